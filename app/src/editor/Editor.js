@@ -1,8 +1,8 @@
 import React from "react";
 import './Editor.css';
 import AceEditor from "react-ace";
-import "ace-builds/src-noconflict/mode-batchfile";
 import "ace-builds/src-noconflict/theme-github";
+import {PseudocodeMode} from "./PseudocodeMode";
 
 class Editor extends React.Component {
     constructor(props) {
@@ -13,6 +13,8 @@ class Editor extends React.Component {
             windowSize: [0, 0]
         };
         this.loadWasm = this.loadWasm.bind(this);
+        this.mode = new PseudocodeMode();
+        this.aceEditor = React.createRef();
     }
 
     componentDidMount() {
@@ -22,18 +24,26 @@ class Editor extends React.Component {
     loadWasm = async () => {
         try {
             const wasm = await import('pseudocompiler');
-            this.setState({wasm: wasm});
+            this.setState({wasm: wasm}, this.highlight);
         } catch (err) {
             console.error(`Unexpected error in loadWasm. [Message: ${err.message}]`);
         }
     };
 
+    highlight = () => {
+        const editor = this.aceEditor.current;
+        if (editor && this.state.wasm.highlight) {
+            this.mode.highlight(editor.editor.getSession(), this.state.wasm.highlight);
+        }
+    };
+
     render() {
         const wasm = this.state.wasm;
         return <>
             <div className="Editor">
                 <AceEditor
-                    mode="batchfile"
+                    ref={this.aceEditor}
+                    mode={this.mode}
                     theme="github"
                     width="100%"
                     name="editor"
@@ -41,14 +51,18 @@ class Editor extends React.Component {
                     onChange={value => {
                         this.setState({
                             code: value
-                        })
+                        }, this.highlight)
                     }}
                     editorProps={{$blockScrolling: true}}/>
             </div>
             <div className="ControlPanel">
                 <button onClick={event => {
                     event.preventDefault();
-                    console.log(wasm.compile(this.state.code))
+                    try {
+                        console.log(wasm.compile(this.state.code))
+                    } catch (err) {
+                        console.error(err)
+                    }
                 }
                 }>RUN CODE
                 </button>
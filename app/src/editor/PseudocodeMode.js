@@ -0,0 +1,74 @@
+import ace from "ace-builds/src-noconflict/ace";
+
+const TextMode = ace.require("ace/mode/text").Mode;
+
+// The Ace token type for each kind of range returned by `highlight` (in the order of
+// `SemanticKind` in `src/parser/highlight.rs`).
+const TOKEN_TYPES = [
+    "keyword",
+    "identifier",
+    "constant.numeric",
+    "string",
+    "keyword.operator",
+    "comment",
+    "entity.name.function",
+    "variable.parameter"
+];
+
+// Splits lines into tokens using the ranges found by the compiler for the whole document, instead
+// of the regular expressions Ace modes normally use.
+class PseudocodeTokenizer {
+    constructor() {
+        this.rows = [];
+    }
+
+    // Stores the ranges returned by `highlight` for `code`, split up by the row they are on.
+    update(code, ranges) {
+        const lineStarts = [0];
+        for (let i = 0; i < code.length; i++) {
+            if (code[i] === "\n") {
+                lineStarts.push(i + 1);
+            }
+        }
+        this.rows = lineStarts.map(() => []);
+        let row = 0;
+        for (let i = 0; i < ranges.length; i += 3) {
+            const [start, end, kind] = [ranges[i], ranges[i + 1], ranges[i + 2]];
+            while (row + 1 < lineStarts.length && lineStarts[row + 1] <= start) {
+                row++;
+            }
+            this.rows[row].push([start - lineStarts[row], end - lineStarts[row], TOKEN_TYPES[kind]]);
+        }
+    }
+
+    getLineTokens(line, state, row) {
+        const tokens = [];
+        let column = 0;
+        for (const [start, end, type] of this.rows[row] || []) {
+            if (start > column) {
+                tokens.push({type: "text", value: line.slice(column, start)});
+            }
+            tokens.push({type: type, value: line.slice(start, end)});
+            column = Math.max(column, Math.min(end, line.length));
+        }
+        if (column < line.length) {
+            tokens.push({type: "text", value: line.slice(column)});
+        }
+        return {tokens: tokens, state: "start"};
+    }
+}
+
+class PseudocodeMode extends TextMode {
+    constructor() {
+        super();
+        this.$tokenizer = new PseudocodeTokenizer();
+    }
+
+    // Re-highlights the whole of `session` using the compiler's `highlight` function.
+    highlight(session, highlight) {
+        this.$tokenizer.update(session.getValue(), highlight(session.getValue()));
+        session.bgTokenizer.start(0);
+    }
+}
+
+export {PseudocodeMode}
@@ -1,8 +1,12 @@
-#![feature(exclusive_range_pattern)]
-
 extern crate wasm_bindgen;
 
-use crate::{parser::lexer::lexer, transpiler::from_tree::from_tree};
+use crate::{
+	parser::{
+		highlight::{classify, encode},
+		lexer::{lexer, lexer_with_config, LexerConfig},
+	},
+	transpiler::from_tree::from_tree,
+};
 use wasm_bindgen::prelude::*;
 
 #[wasm_bindgen]
@@ -10,15 +14,41 @@ extern "C" {
 	pub fn alert(s: &str);
 }
 
-mod parser;
+/// The lexer and the parser. These are public so that editors can relex the source incrementally
+/// (see `parser::incremental::relex`) as it is edited.
+pub mod parser;
 mod tests;
 mod transpiler;
 
 /// Compiles a function from pseudocode into Javascript, which can then be executed in the browser
 /// using `eval`.
+///
+/// If the input cannot be lexed, an error containing a description of every problem found (one per
+/// line) is returned, which is thrown as an exception on the Javascript side. Warnings from the
+/// parser (such as indentation which does not match the blocks) are logged to the console by the
+/// start of the output.
+#[wasm_bindgen]
+pub fn compile(input: String) -> Result<String, JsValue> {
+	let mut lexed = lexer(&input).map_err(|errors| {
+		JsValue::from_str(
+			&errors.iter().map(|error| error.message()).collect::<Vec<String>>().join("\n"),
+		)
+	})?;
+	let (parser_output, start_node, warnings) = parser::parser::parser_with_warnings(&mut lexed);
+	let warnings: String =
+		warnings.iter().map(|warning| format!("console.warn({:?});\n", warning.message)).collect();
+	Ok(warnings + &from_tree(parser_output, start_node))
+}
+
+/// Classifies the input for syntax highlighting, in the same way as the compiler understands it.
+///
+/// The result is a flat list of `start, end, kind` triples, where `start` and `end` are offsets
+/// (in UTF-16 code units, as Javascript strings are indexed) into the input, and `kind` is a
+/// `SemanticKind` discriminant. Input which cannot be lexed is skipped over, so that the rest of
+/// the input can still be highlighted while it is being edited.
 #[wasm_bindgen]
-pub fn compile(input: String) -> String {
-	let mut lexed = lexer(&input);
-	let (mut parser_output, start_node) = parser::parser::parser(&mut lexed);
-	return from_tree(parser_output, start_node);
+pub fn highlight(input: String) -> Vec<u32> {
+	let config = LexerConfig { recover: true, ..LexerConfig::default() };
+	let tokens = lexer_with_config(&input, config).unwrap_or_default();
+	encode(&input, &classify(&tokens))
 }
@@ -18,3 +18,9 @@ impl NumberState {
 		self.decimal = val;
 	}
 }
+
+impl Default for NumberState {
+	fn default() -> Self {
+		Self::new()
+	}
+}
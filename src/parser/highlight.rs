@@ -0,0 +1,106 @@
+use crate::parser::lexer::{Keyword, LiteralValue, Span, Token, TokenValue, TriviaKind};
+
+/// The kinds of text which the editor highlights differently. The discriminants are the numbers
+/// which are passed to Javascript, so should not be changed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[repr(u32)]
+pub enum SemanticKind {
+	Keyword = 0,
+	Identifier = 1,
+	Number = 2,
+	String = 3,
+	Operator = 4,
+	Comment = 5,
+	FunctionName = 6,
+	Parameter = 7,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SemanticToken {
+	pub span: Span,
+	pub kind: SemanticKind,
+}
+
+/// Classifies the tokens produced by the lexer for highlighting, in the order they appear in the
+/// source. Tokens which are not highlighted (such as brackets and new lines) are left out.
+///
+/// Identifiers are classified as function names if they are called or declared, and as parameters
+/// if they are declared in the parameter list of the function or procedure they are used in.
+pub fn classify(tokens: &[TokenValue]) -> Vec<SemanticToken> {
+	let mut classified = Vec::new();
+	// The parameters of the function or procedure which is being classified.
+	let mut parameters: Vec<&str> = Vec::new();
+	// Whether the next identifier is the name of a function or procedure being declared.
+	let mut expect_name = false;
+	// Whether the tokens are inside the parameter list of a declaration.
+	let mut in_parameters = false;
+	for (index, token) in tokens.iter().enumerate() {
+		for trivia in &token.leading_trivia {
+			if trivia.kind == TriviaKind::Comment {
+				classified.push(SemanticToken { span: trivia.span, kind: SemanticKind::Comment });
+			}
+		}
+		let called = tokens.get(index + 1).is_some_and(|next| next.token == Token::OpenBracket);
+		let kind = match &token.token {
+			Token::Keyword(keyword) => {
+				match keyword {
+					Keyword::Function | Keyword::Procedure => {
+						expect_name = true;
+						parameters.clear();
+					}
+					Keyword::EndFunction | Keyword::EndProcedure => parameters.clear(),
+					_ => {}
+				}
+				SemanticKind::Keyword
+			}
+			Token::Identifier(_) if expect_name => {
+				expect_name = false;
+				in_parameters = called;
+				SemanticKind::FunctionName
+			}
+			Token::Identifier(name) if in_parameters => {
+				parameters.push(name);
+				SemanticKind::Parameter
+			}
+			Token::Identifier(_) | Token::FunctionCall(_) if called => SemanticKind::FunctionName,
+			Token::Identifier(name) if parameters.contains(&name.as_str()) => {
+				SemanticKind::Parameter
+			}
+			Token::Identifier(_) => SemanticKind::Identifier,
+			Token::FunctionCall(_) => SemanticKind::FunctionName,
+			Token::Operator(_) => SemanticKind::Operator,
+			Token::Literal(LiteralValue::Number(_)) => SemanticKind::Number,
+			Token::Literal(LiteralValue::String(_)) => SemanticKind::String,
+			Token::Literal(LiteralValue::Bool(_)) => SemanticKind::Keyword,
+			Token::Comment(_) => SemanticKind::Comment,
+			Token::CloseBracket => {
+				in_parameters = false;
+				continue;
+			}
+			_ => continue,
+		};
+		classified.push(SemanticToken { span: token.span, kind });
+	}
+	classified
+}
+
+/// Flattens classified tokens into `[start, end, kind, start, end, kind, ...]`, where `start` and
+/// `end` are offsets in UTF-16 code units (which is how Javascript indexes strings) into `source`.
+pub fn encode(source: &str, classified: &[SemanticToken]) -> Vec<u32> {
+	let mut encoded = Vec::with_capacity(classified.len() * 3);
+	// The byte offset and the UTF-16 offset of the last position converted. The tokens are in
+	// order, so each conversion can carry on from the last one.
+	let mut byte = 0;
+	let mut utf16 = 0;
+	let mut convert = |offset: usize| {
+		utf16 += source[byte..offset].encode_utf16().count() as u32;
+		byte = offset;
+		utf16
+	};
+	for token in classified {
+		encoded.push(convert(token.span.start));
+		encoded.push(convert(token.span.end));
+		encoded.push(token.kind as u32);
+	}
+	encoded
+}
@@ -0,0 +1,214 @@
+use crate::parser::lexer::{
+	indentation_width, LexError, Lexer, LexerConfig, Loc, Span, Token, TokenValue,
+};
+use std::ops::Range;
+
+/// A change to the source code: the bytes from `start` to `end` (in the old source) are replaced
+/// with `replacement`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextEdit {
+	pub start: usize,
+	pub end: usize,
+	pub replacement: String,
+}
+
+impl TextEdit {
+	pub fn new(start: usize, end: usize, replacement: &str) -> Self {
+		Self { start, end, replacement: String::from(replacement) }
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Relexed {
+	pub tokens: Vec<TokenValue>,
+	/// The indices in `tokens` of the tokens which were relexed. The tokens either side of this
+	/// range were carried over from the old tokens (with their positions moved to match the edit).
+	pub changed: Range<usize>,
+}
+
+/// Updates `old_tokens` (the result of lexing the source before `edit` was made, with the same
+/// configuration) to match `new_source` (the source after the edit was made).
+///
+/// Only the lines from the one containing the start of the edit onwards are relexed, and relexing
+/// stops at the first line boundary after the edit at which the lexer is back in the same state as
+/// it was in before the edit.
+pub fn relex(
+	old_tokens: &[TokenValue],
+	new_source: &str,
+	edit: &TextEdit,
+	config: LexerConfig,
+) -> Result<Relexed, Vec<LexError>> {
+	let delta = edit.replacement.len() as isize - (edit.end - edit.start) as isize;
+	// The source before the edit is unchanged, so the indentation of every line before it can be
+	// found in the new source. Lines after the edit can be found by moving them by `delta`.
+	let indentation = |indent: &TokenValue| {
+		let delta = if indent.span.end <= edit.start {
+			0
+		} else if line_start(indent) >= edit.end {
+			delta
+		} else {
+			return None;
+		};
+		let start = (line_start(indent) as isize + delta) as usize;
+		let end = (indent.span.end as isize + delta) as usize;
+		Some(&new_source[start..end])
+	};
+
+	// Relexing starts after the last new line which comes before the edit.
+	let first = old_tokens
+		.iter()
+		.rposition(|token| ends_line(token) && token.span.end <= edit.start)
+		.map_or(0, |index| index + 1);
+	let (offset, loc) = match first {
+		0 => (0, Loc::new(1, 1)),
+		_ => (old_tokens[first - 1].span.end, old_tokens[first - 1].span.end_loc),
+	};
+	let mut old_state = IndentState::new(config.tab_width);
+	for token in &old_tokens[..first] {
+		old_state.apply(token, &indentation);
+	}
+
+	let mut lexer = Lexer::resume(
+		new_source,
+		config,
+		offset,
+		loc,
+		old_state.widths().unwrap(),
+		old_state.indent_char.unwrap(),
+	);
+	let mut relexed = Vec::new();
+	let mut errors = Vec::new();
+	// The index of the next old token to be compared against, and the state of the old lexer just
+	// before that token.
+	let mut old_index = first;
+	let edit_end = edit.start + edit.replacement.len();
+	let mut resumed = None;
+	while let Some(item) = lexer.next() {
+		let token = match item {
+			Ok(token) => token,
+			Err(error) => {
+				errors.push(error);
+				continue;
+			}
+		};
+		let new_line = ends_line(&token) && token.span.start >= edit_end;
+		let end = token.span.end;
+		let end_line = token.span.end_loc.line_num;
+		relexed.push(token);
+		if !new_line {
+			continue;
+		}
+		// Find the new line which ends the same line in the old tokens, if there is one.
+		let old_end = (end as isize - delta) as usize;
+		while old_index < old_tokens.len() && old_tokens[old_index].span.end < old_end {
+			old_state.apply(&old_tokens[old_index], &indentation);
+			old_index += 1;
+		}
+		if old_index == old_tokens.len() {
+			break;
+		}
+		let old_new_line = &old_tokens[old_index];
+		if ends_line(old_new_line)
+			&& old_new_line.span.end == old_end
+			&& old_state.widths().as_deref() == Some(lexer.indent_stack())
+			&& old_state.indent_char == Some(lexer.indent_char())
+		{
+			let line_delta = end_line - old_new_line.span.end_loc.line_num;
+			resumed = Some((old_index + 1, line_delta));
+			break;
+		}
+	}
+	if !errors.is_empty() {
+		return Err(errors);
+	}
+
+	let mut tokens = old_tokens[..first].to_vec();
+	let changed = first..first + relexed.len();
+	tokens.append(&mut relexed);
+	if let Some((old_index, line_delta)) = resumed {
+		for token in &old_tokens[old_index..] {
+			tokens.push(shift(token, delta, line_delta));
+		}
+	}
+	Ok(Relexed { tokens, changed })
+}
+
+/// Whether `token` is a new line in the source. The empty new line which the lexer adds at the end
+/// of input which does not end with one is not, as text added after it joins the last line.
+fn ends_line(token: &TokenValue) -> bool {
+	token.token == Token::NewLine && token.span.start < token.span.end
+}
+
+/// The byte offset of the start of the line which `token` is on. This is only valid for tokens at
+/// the start of a line which are preceded by nothing but whitespace, such as indents.
+fn line_start(token: &TokenValue) -> usize {
+	token.span.end - (token.span.end_loc.column_num as usize - 1)
+}
+
+/// The indentation state of the lexer at a point in a list of tokens, found by replaying the
+/// `Indent` and `Dedent` tokens before that point. Throughout, `None` stands for something which
+/// depended on the edited text, and so can no longer be found.
+struct IndentState {
+	tab_width: usize,
+	/// The widths of the indentation of each enclosing indented line.
+	widths: Vec<Option<usize>>,
+	/// The character the first (consistently) indented line was indented with.
+	indent_char: Option<Option<char>>,
+}
+
+impl IndentState {
+	fn new(tab_width: usize) -> Self {
+		Self { tab_width, widths: vec![Some(0)], indent_char: Some(None) }
+	}
+	/// Updates the state to account for `token`, where `indentation` returns the whitespace at the
+	/// start of an indented line.
+	fn apply<'a>(
+		&mut self,
+		token: &TokenValue,
+		indentation: &impl Fn(&TokenValue) -> Option<&'a str>,
+	) {
+		match token.token {
+			Token::Indent => {
+				let whitespace = indentation(token);
+				self.widths.push(whitespace.map(|text| indentation_width(text, self.tab_width)));
+				// Indents for inconsistently indented lines are empty, and do not set the character.
+				if self.indent_char == Some(None) && token.span.start < token.span.end {
+					self.indent_char = whitespace.map(|text| text.chars().next());
+				}
+			}
+			Token::Dedent => {
+				self.widths.pop();
+			}
+			_ => {}
+		}
+	}
+	/// Returns the widths, if they are all known.
+	fn widths(&self) -> Option<Vec<usize>> {
+		self.widths.iter().cloned().collect()
+	}
+}
+
+/// Moves a token which comes after an edit to account for the text inserted or removed by it.
+fn shift(token: &TokenValue, delta: isize, line_delta: i32) -> TokenValue {
+	let mut token = token.clone();
+	token.span = shift_span(token.span, delta, line_delta);
+	for trivia in &mut token.leading_trivia {
+		trivia.span = shift_span(trivia.span, delta, line_delta);
+	}
+	if let Token::Error(error) = &mut token.token {
+		error.loc_mut().line_num += line_delta;
+	}
+	token
+}
+
+fn shift_span(span: Span, delta: isize, line_delta: i32) -> Span {
+	let mut shifted = Span::new(
+		(span.start as isize + delta) as usize,
+		(span.end as isize + delta) as usize,
+		span.start_loc,
+		span.end_loc,
+	);
+	shifted.start_loc.line_num += line_delta;
+	shifted.end_loc.line_num += line_delta;
+	shifted
+}
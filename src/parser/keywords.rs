@@ -0,0 +1,110 @@
+use crate::parser::lexer::{Keyword, LiteralValue, Operator, Token};
+use std::collections::HashMap;
+
+/// The exam board whose flavour of pseudocode is being lexed.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Dialect {
+	/// The OCR pseudocode guide, which writes keywords in lower case (`endif`).
+	Ocr,
+	/// The AQA pseudocode guide, which writes keywords in upper case (`ENDIF`) and uses `REPEAT` and
+	/// `SUBROUTINE`.
+	Aqa,
+	/// The Cambridge pseudocode guide, which writes keywords in upper case (`ENDWHILE`, `NEXT`) and
+	/// uses `REPEAT`, `OTHERWISE` and `ENDCASE`.
+	Cambridge,
+}
+
+/// Every word with a special meaning, spelt as it is in the OCR guide.
+const WORDS: &[(&str, Token)] = &[
+	("true", Token::Literal(LiteralValue::Bool(true))),
+	("false", Token::Literal(LiteralValue::Bool(false))),
+	("and", Token::Operator(Operator::And)),
+	("or", Token::Operator(Operator::Or)),
+	("not", Token::Operator(Operator::Not)),
+	("DIV", Token::Operator(Operator::IntegerDivide)),
+	("MOD", Token::Operator(Operator::Modulo)),
+	("in", Token::Operator(Operator::In)),
+	("if", Token::Keyword(Keyword::If)),
+	("then", Token::Keyword(Keyword::Then)),
+	("elseif", Token::Keyword(Keyword::ElseIf)),
+	("else", Token::Keyword(Keyword::Else)),
+	("endif", Token::Keyword(Keyword::EndIf)),
+	("for", Token::Keyword(Keyword::For)),
+	("to", Token::Keyword(Keyword::To)),
+	("step", Token::Keyword(Keyword::Step)),
+	("next", Token::Keyword(Keyword::Next)),
+	("endfor", Token::Keyword(Keyword::EndFor)),
+	("while", Token::Keyword(Keyword::While)),
+	("endwhile", Token::Keyword(Keyword::EndWhile)),
+	("do", Token::Keyword(Keyword::Do)),
+	("until", Token::Keyword(Keyword::Until)),
+	("switch", Token::Keyword(Keyword::Switch)),
+	("case", Token::Keyword(Keyword::Case)),
+	("default", Token::Keyword(Keyword::Default)),
+	("endswitch", Token::Keyword(Keyword::EndSwitch)),
+	("function", Token::Keyword(Keyword::Function)),
+	("endfunction", Token::Keyword(Keyword::EndFunction)),
+	("procedure", Token::Keyword(Keyword::Procedure)),
+	("endprocedure", Token::Keyword(Keyword::EndProcedure)),
+	("return", Token::Keyword(Keyword::Return)),
+	("global", Token::Keyword(Keyword::Global)),
+	("array", Token::Keyword(Keyword::Array)),
+	("class", Token::Keyword(Keyword::Class)),
+	("endclass", Token::Keyword(Keyword::EndClass)),
+	("inherits", Token::Keyword(Keyword::Inherits)),
+	("new", Token::Keyword(Keyword::New)),
+	("super", Token::Keyword(Keyword::Super)),
+	("public", Token::Keyword(Keyword::Public)),
+	("private", Token::Keyword(Keyword::Private)),
+	("byVal", Token::Keyword(Keyword::ByVal)),
+	("byRef", Token::Keyword(Keyword::ByRef)),
+];
+
+const AQA_ALIASES: &[(&str, Token)] = &[
+	("REPEAT", Token::Keyword(Keyword::Do)),
+	("SUBROUTINE", Token::Keyword(Keyword::Function)),
+	("ENDSUBROUTINE", Token::Keyword(Keyword::EndFunction)),
+];
+
+const CAMBRIDGE_ALIASES: &[(&str, Token)] = &[
+	("REPEAT", Token::Keyword(Keyword::Do)),
+	("OTHERWISE", Token::Keyword(Keyword::Default)),
+	("ENDCASE", Token::Keyword(Keyword::EndSwitch)),
+];
+
+/// Maps words to the keyword (or operator or literal) tokens they stand for in a given dialect.
+#[derive(Debug, Clone)]
+pub struct KeywordTable {
+	words: HashMap<String, Token>,
+	case_sensitive: bool,
+}
+
+impl KeywordTable {
+	pub fn new(dialect: Dialect, case_sensitive: bool) -> Self {
+		let (upper_case, aliases) = match dialect {
+			Dialect::Ocr => (false, &[][..]),
+			Dialect::Aqa => (true, AQA_ALIASES),
+			Dialect::Cambridge => (true, CAMBRIDGE_ALIASES),
+		};
+		let mut words = HashMap::new();
+		for (word, token) in WORDS.iter().chain(aliases.iter()) {
+			let word = if !case_sensitive {
+				word.to_lowercase()
+			} else if upper_case {
+				word.to_uppercase()
+			} else {
+				String::from(*word)
+			};
+			words.insert(word, token.clone());
+		}
+		Self { words, case_sensitive }
+	}
+	/// Returns the token which `word` stands for, or `None` if it is an ordinary identifier.
+	pub fn lookup(&self, word: &str) -> Option<Token> {
+		if self.case_sensitive {
+			self.words.get(word).cloned()
+		} else {
+			self.words.get(&word.to_lowercase()).cloned()
+		}
+	}
+}
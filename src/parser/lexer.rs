@@ -1,18 +1,24 @@
-use crate::parser::helpers::NumberState;
-
-use super::helpers;
-use crate::parser::lexer::Token::Literal;
+use crate::parser::{
+	helpers::NumberState,
+	keywords::{Dialect, KeywordTable},
+};
+use std::{collections::VecDeque, iter::Peekable, str::CharIndices};
+use unicode_xid::UnicodeXID;
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Number {
+	/// The digits of the exponent, preceded by a '-' if the exponent is negative.
 	pub exponent: Option<String>,
 	pub decimal: Option<String>,
 	pub base: String,
+	/// The radix the digits are written in. This is 10 unless the number was written with a `0x`
+	/// (16), `0b` (2) or `0o` (8) prefix, in which case only `base` is used.
+	pub radix: u32,
 }
 
 impl Number {
 	pub fn new() -> Self {
-		Self { exponent: None, decimal: None, base: String::from("") }
+		Self { exponent: None, decimal: None, base: String::from(""), radix: 10 }
 	}
 	pub fn from_values(exponent: &Option<String>, decimal: &Option<String>, base: &String) -> Self {
 		Self {
@@ -25,10 +31,17 @@ impl Number {
 				None => None,
 			},
 			base: String::from(base),
+			radix: 10,
 		}
 	}
 }
 
+impl Default for Number {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum LiteralValue {
 	Number(Number),
@@ -40,17 +53,38 @@ pub enum LiteralValue {
 pub enum Keyword {
 	If,
 	ElseIf,
+	Else,
 	EndIf,
 	For,
 	EndFor,
+	Next,
+	Step,
 	While,
 	EndWhile,
 	Function,
 	EndFunction,
+	Procedure,
+	EndProcedure,
 	Return,
 	Do,
+	Until,
 	To,
 	Then,
+	Switch,
+	Case,
+	Default,
+	EndSwitch,
+	Global,
+	Array,
+	Class,
+	EndClass,
+	Inherits,
+	New,
+	Super,
+	Public,
+	Private,
+	ByVal,
+	ByRef,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -73,6 +107,7 @@ pub enum Operator {
 	Divide,
 	IntegerDivide,
 	Modulo,
+	Exponent,
 	And,
 	Or,
 	Not,
@@ -86,6 +121,8 @@ pub enum Operator {
 	LessThanOrEqualTo,
 	// Not used as part of the lexer (only in the parser)
 	Empty,
+	// Not used as part of the lexer (only in the parser, for a unary minus)
+	Negate,
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -97,468 +134,795 @@ pub enum Token {
 	FunctionCall(String),
 	Literal(LiteralValue),
 	Comment(String),
+	/// Input which could not be lexed. These are only produced when the lexer is recovering from
+	/// errors (see `LexerConfig::recover`), and span the whole of the offending text.
+	Error(LexError),
 	OpenBracket,
 	CloseBracket,
 	OpenSquareBracket,
 	CloseSquareBracket,
+	Colon,
 	Comma,
 	Dot,
 	EndOfSequence,
 	NewLine,
-	Tab,
+	/// The start of a line which is indented further than the line before it.
+	Indent,
+	/// The end of an indented region. One of these is produced for each enclosing `Indent` which a
+	/// line's indentation drops below.
+	Dedent,
+}
+
+/// The region of the source code which a token was lexed from.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Span {
+	/// The byte offset of the first character of the token.
+	pub start: usize,
+	/// The byte offset one past the last character of the token.
+	pub end: usize,
+	/// The line and column of the first character of the token.
+	pub start_loc: Loc,
+	/// The line and column one past the last character of the token.
+	pub end_loc: Loc,
+}
+
+impl Span {
+	pub fn new(start: usize, end: usize, start_loc: Loc, end_loc: Loc) -> Self {
+		Self { start, end, start_loc, end_loc }
+	}
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TriviaKind {
+	/// A run of spaces and tabs.
+	Whitespace,
+	/// A comment, including the leading '//'.
+	Comment,
+	/// A backslash, any spaces following it and the new line which it continues onto.
+	LineContinuation,
+}
+
+/// Source text which has no meaning to the parser, but which is kept in lossless mode so that the
+/// original text can be reproduced.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Trivia {
+	pub kind: TriviaKind,
+	pub text: String,
+	pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct TokenValue {
 	pub token: Token,
-	pub loc: Loc,
+	pub span: Span,
+	/// The trivia between the previous token and this one. This is only recorded in lossless mode.
+	pub leading_trivia: Vec<Trivia>,
+	/// The source text of the token. This is only recorded in lossless mode.
+	pub text: String,
 }
 
 impl TokenValue {
-	pub fn new(token: Token, line: i32, col: i32) -> Self {
-		Self { token, loc: Loc::new(line, col) }
+	pub fn new(token: Token, span: Span) -> Self {
+		Self { token, span, leading_trivia: Vec::new(), text: String::new() }
 	}
 }
 
-pub fn get_next(input: &mut String) -> char {
-	let first = input.chars().next().unwrap();
-	input.remove(0);
-	first
+/// Reproduces the source text from tokens which were lexed in lossless mode, by concatenating each
+/// token's leading trivia and text.
+pub fn to_source(tokens: &[TokenValue]) -> String {
+	let mut source = String::new();
+	for token in tokens {
+		for trivia in &token.leading_trivia {
+			source.push_str(&trivia.text);
+		}
+		source.push_str(&token.text);
+	}
+	source
 }
 
-/// Runs a lexical analysis procedure, returning a list of token values which can be used for further processing.
-pub fn lexer(input: &String) -> Vec<TokenValue> {
-	// Reverse direction of string
-	let mut input_stack: String = String::from(input);
-	let mut output_stack: Vec<TokenValue> = Vec::new();
-	let mut pos_number = 0;
-	let mut loc = Loc::new(1, 0);
-	while input_stack.len() > 0 {
-		let mut top = input_stack.chars().next().unwrap();
-		match top {
-			// match identifier
-			'a'..='z' | 'A'..='Z' => {
-				let mut finished = false;
-				let mut identifier = String::from("");
-				while !finished {
-					if input_stack.len() > 0 {
-						top = get_next(&mut input_stack);
-						loc.column_num += 1;
-						match top {
-							'a'..='z' | 'A'..='Z' => {
-								identifier.push(top);
-							}
-							'0'..'9' => identifier.push(top),
-							' ' => {
-								finished = true;
-							}
-							'/' | '+' | '-' | '*' | '.' | '=' => {
-								input_stack = top.to_string() + &input_stack;
-								finished = true;
-							}
-							'\n' => {
-								loc.line_num += 1;
-								finished = true;
-							}
-							_ => panic!(
-								"Unexpected token {} on line {}, column {}.",
-								top, loc.column_num, loc.line_num
-							),
-						}
+/// An error encountered while lexing, carrying the location at which it occurred.
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexError {
+	/// A character which cannot start (or continue) any token.
+	UnexpectedChar { found: char, loc: Loc },
+	/// A string literal which was not closed before the end of the line. The location is that of
+	/// the opening quote.
+	UnterminatedString { loc: Loc },
+	/// An escape sequence inside a string literal which is not recognised.
+	UnknownEscape { found: char, loc: Loc },
+	/// A number literal which does not follow the number syntax.
+	MalformedNumber { reason: String, loc: Loc },
+	/// A backslash which is not followed by the end of the line.
+	BadLineContinuation { loc: Loc },
+	/// A '!' which is not followed by an '='.
+	ExpectedEquals { loc: Loc },
+	/// Indentation which mixes tabs and spaces, or which uses a different one of them to the
+	/// indentation of earlier lines.
+	InconsistentIndentation { loc: Loc },
+	/// A line which is indented less than the line before it, but not as little as any enclosing
+	/// line. This is only reported if `strict_indentation` is set.
+	BadDedent { loc: Loc },
+}
+
+impl LexError {
+	pub fn loc(&self) -> Loc {
+		match self {
+			LexError::UnexpectedChar { loc, .. }
+			| LexError::UnterminatedString { loc }
+			| LexError::UnknownEscape { loc, .. }
+			| LexError::MalformedNumber { loc, .. }
+			| LexError::BadLineContinuation { loc }
+			| LexError::ExpectedEquals { loc }
+			| LexError::InconsistentIndentation { loc }
+			| LexError::BadDedent { loc } => *loc,
+		}
+	}
+	pub(crate) fn loc_mut(&mut self) -> &mut Loc {
+		match self {
+			LexError::UnexpectedChar { loc, .. }
+			| LexError::UnterminatedString { loc }
+			| LexError::UnknownEscape { loc, .. }
+			| LexError::MalformedNumber { loc, .. }
+			| LexError::BadLineContinuation { loc }
+			| LexError::ExpectedEquals { loc }
+			| LexError::InconsistentIndentation { loc }
+			| LexError::BadDedent { loc } => loc,
+		}
+	}
+	/// A human readable description of the error, including its location.
+	pub fn message(&self) -> String {
+		let loc = self.loc();
+		match self {
+			LexError::UnexpectedChar { found, .. } => format!(
+				"Unexpected character '{}' on line {}, column {}.",
+				found, loc.line_num, loc.column_num
+			),
+			LexError::UnterminatedString { .. } => format!(
+				"Unterminated string starting on line {}, column {}.",
+				loc.line_num, loc.column_num
+			),
+			LexError::UnknownEscape { found, .. } => format!(
+				"Unknown escape sequence '\\{}' on line {}, column {}.",
+				found, loc.line_num, loc.column_num
+			),
+			LexError::MalformedNumber { reason, .. } => format!(
+				"Malformed number on line {}, column {}: {}.",
+				loc.line_num, loc.column_num, reason
+			),
+			LexError::BadLineContinuation { .. } => format!(
+				"Expected a new line (or space) following the backslash on line {}, column {}.",
+				loc.line_num, loc.column_num
+			),
+			LexError::ExpectedEquals { .. } => format!(
+				"Expected an equals following a '!' on line {}, column {}. Note that the 'not' \
+				 keyword should be used for the 'not' logical operator.",
+				loc.line_num, loc.column_num
+			),
+			LexError::InconsistentIndentation { .. } => format!(
+				"Inconsistent use of tabs and spaces in the indentation on line {}.",
+				loc.line_num
+			),
+			LexError::BadDedent { .. } => format!(
+				"The indentation on line {} does not match the indentation of any enclosing block.",
+				loc.line_num
+			),
+		}
+	}
+}
+
+impl std::fmt::Display for LexError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.message())
+	}
+}
+
+/// Identifiers follow the Unicode rules for identifiers, but may also start with an underscore.
+fn is_identifier_start(c: char) -> bool {
+	c == '_' || UnicodeXID::is_xid_start(c)
+}
+
+fn is_identifier_continue(c: char) -> bool {
+	UnicodeXID::is_xid_continue(c)
+}
+
+/// The width of the indentation `whitespace` (which should consist of only tabs and spaces), with
+/// each tab moving to the next multiple of `tab_width`.
+pub(crate) fn indentation_width(whitespace: &str, tab_width: usize) -> usize {
+	whitespace.chars().fold(0, |width, c| match c {
+		'\t' => (width / tab_width + 1) * tab_width,
+		_ => width + 1,
+	})
+}
+
+fn radix_name(radix: u32) -> &'static str {
+	match radix {
+		2 => "binary",
+		8 => "octal",
+		16 => "hexadecimal",
+		_ => "decimal",
+	}
+}
+
+/// Options controlling how the lexer treats its input.
+#[derive(Debug, Clone)]
+pub struct LexerConfig {
+	/// If this is false, keywords are recognised however they are capitalised (so `IF`, `If` and `if`
+	/// are all the same keyword). Identifiers are always case sensitive.
+	pub case_sensitive: bool,
+	/// The exam board whose spelling of keywords is accepted.
+	pub dialect: Dialect,
+	/// If this is true, problems in the input are reported as `Token::Error` tokens in the token
+	/// stream (rather than as `Err` items), so that lexing always produces a usable list of tokens.
+	pub recover: bool,
+	/// If this is true, whitespace, comments and line continuations are kept as trivia attached to
+	/// the following token, and each token records its source text, so that `to_source` can
+	/// reproduce the input byte-for-byte. Comments are then trivia rather than `Token::Comment`s.
+	/// The input is only reproduced exactly if it has no errors, or if `recover` is also set.
+	pub lossless: bool,
+	/// The number of columns which a tab in the indentation at the start of a line is counted as.
+	pub tab_width: usize,
+	/// If this is true, a line which is indented less than the line before it, but not as little as
+	/// any enclosing line, is an error. Otherwise it closes the blocks it is indented less than.
+	pub strict_indentation: bool,
+}
+
+impl Default for LexerConfig {
+	fn default() -> Self {
+		Self {
+			case_sensitive: false,
+			dialect: Dialect::Ocr,
+			recover: false,
+			lossless: false,
+			tab_width: 4,
+			strict_indentation: false,
+		}
+	}
+}
+
+/// A streaming lexer, which borrows the input and produces tokens on demand.
+///
+/// Problems in the input are yielded as `Err` items (or as `Token::Error` tokens when recovering),
+/// after which lexing carries on, so that every error in the input can be found. The final item is
+/// always a `Token::EndOfSequence`.
+pub struct Lexer<'a> {
+	input: &'a str,
+	chars: Peekable<CharIndices<'a>>,
+	/// The byte offset in `input` which `chars` starts from.
+	origin: usize,
+	loc: Loc,
+	keywords: KeywordTable,
+	recover: bool,
+	lossless: bool,
+	/// Trivia which has been lexed in lossless mode, but not yet attached to a token.
+	trivia: Vec<Trivia>,
+	tab_width: usize,
+	strict_indentation: bool,
+	/// The widths of the indentation of each enclosing indented line, starting with 0.
+	indent_stack: Vec<usize>,
+	/// The character (tab or space) which the first indented line was indented with.
+	indent_char: Option<char>,
+	at_line_start: bool,
+	/// Whether the last token was a new line (or no tokens have been produced yet).
+	line_ended: bool,
+	/// Items which have been lexed but not yet returned from `next`.
+	pending: VecDeque<Result<TokenValue, LexError>>,
+	finished: bool,
+}
+
+impl<'a> Lexer<'a> {
+	pub fn new(input: &'a str) -> Self {
+		Self::with_config(input, LexerConfig::default())
+	}
+	pub fn with_config(input: &'a str, config: LexerConfig) -> Self {
+		Self {
+			input,
+			chars: input.char_indices().peekable(),
+			origin: 0,
+			loc: Loc::new(1, 1),
+			keywords: KeywordTable::new(config.dialect, config.case_sensitive),
+			recover: config.recover,
+			lossless: config.lossless,
+			trivia: Vec::new(),
+			tab_width: config.tab_width,
+			strict_indentation: config.strict_indentation,
+			indent_stack: vec![0],
+			indent_char: None,
+			at_line_start: true,
+			line_ended: true,
+			pending: VecDeque::new(),
+			finished: false,
+		}
+	}
+	/// Creates a lexer which starts lexing `input` from the start of a line part of the way through
+	/// it, in the same state as a lexer which had lexed everything before that line would be in.
+	pub(crate) fn resume(
+		input: &'a str,
+		config: LexerConfig,
+		offset: usize,
+		loc: Loc,
+		indent_stack: Vec<usize>,
+		indent_char: Option<char>,
+	) -> Self {
+		let mut lexer = Self::with_config(input, config);
+		lexer.chars = input[offset..].char_indices().peekable();
+		lexer.origin = offset;
+		lexer.loc = loc;
+		lexer.indent_stack = indent_stack;
+		lexer.indent_char = indent_char;
+		lexer
+	}
+	pub(crate) fn indent_stack(&self) -> &[usize] {
+		&self.indent_stack
+	}
+	pub(crate) fn indent_char(&self) -> Option<char> {
+		self.indent_char
+	}
+	fn peek(&mut self) -> Option<char> {
+		self.chars.peek().map(|(_, c)| *c)
+	}
+	fn peek_second(&mut self) -> Option<char> {
+		let mut chars = self.chars.clone();
+		chars.next();
+		chars.next().map(|(_, c)| c)
+	}
+	/// The byte offset of the next character to be consumed.
+	fn offset(&mut self) -> usize {
+		match self.chars.peek() {
+			Some((offset, _)) => self.origin + *offset,
+			None => self.input.len(),
+		}
+	}
+	/// Consumes the next character, returning it. All movement through the input goes through this
+	/// method, so that line and column numbers are always kept in step with the byte offset.
+	fn bump(&mut self) -> Option<char> {
+		let (_, next) = self.chars.next()?;
+		if next == '\n' {
+			self.loc.line_num += 1;
+			self.loc.column_num = 1;
+		} else {
+			self.loc.column_num += 1;
+		}
+		Some(next)
+	}
+	fn position(&mut self) -> (usize, Loc) {
+		(self.offset(), self.loc)
+	}
+	/// Returns the span running from `start` (obtained from `position`) to the current position.
+	fn span_from(&mut self, start: (usize, Loc)) -> Span {
+		Span::new(start.0, self.offset(), start.1, self.loc)
+	}
+	fn push_token(&mut self, token: Token, start: (usize, Loc)) {
+		let span = self.span_from(start);
+		self.line_ended = token == Token::NewLine;
+		let mut token = TokenValue::new(token, span);
+		if self.lossless {
+			token.leading_trivia = std::mem::take(&mut self.trivia);
+			token.text = String::from(&self.input[span.start..span.end]);
+		}
+		self.pending.push_back(Ok(token));
+	}
+	/// Records the text running from `start` to the current position as trivia, if in lossless mode.
+	fn push_trivia(&mut self, kind: TriviaKind, start: (usize, Loc)) {
+		if self.lossless {
+			let span = self.span_from(start);
+			let text = String::from(&self.input[span.start..span.end]);
+			self.trivia.push(Trivia { kind, text, span });
+		}
+	}
+	/// Reports a problem with the text running from `start` to the current position. Each piece of
+	/// text is reported at most once, so that when recovering the error tokens never overlap.
+	fn push_error(&mut self, error: LexError, start: (usize, Loc)) {
+		if self.recover {
+			self.push_token(Token::Error(error), start);
+		} else {
+			self.pending.push_back(Err(error));
+		}
+	}
+	/// Consumes the next character and emits `token` for it.
+	fn single(&mut self, token: Token, start: (usize, Loc)) {
+		self.bump();
+		self.push_token(token, start);
+	}
+	/// Consumes the next character, and emits `double` if it is followed by `second` (consuming that
+	/// too) or `single` otherwise.
+	fn single_or_double(
+		&mut self,
+		second: char,
+		double: Token,
+		single: Token,
+		start: (usize, Loc),
+	) {
+		self.bump();
+		if self.peek() == Some(second) {
+			self.bump();
+			self.push_token(double, start);
+		} else {
+			self.push_token(single, start);
+		}
+	}
+	fn lex_identifier(&mut self, start: (usize, Loc)) {
+		while let Some(next) = self.peek() {
+			if !is_identifier_continue(next) {
+				break;
+			}
+			self.bump();
+		}
+		let identifier = &self.input[start.0..self.offset()];
+		let token = match self.keywords.lookup(identifier) {
+			Some(token) => token,
+			None => Token::Identifier(String::from(identifier)),
+		};
+		self.push_token(token, start);
+	}
+	fn lex_number(&mut self, start: (usize, Loc)) {
+		let radix = match (self.peek(), self.peek_second()) {
+			(Some('0'), Some('x')) | (Some('0'), Some('X')) => 16,
+			(Some('0'), Some('b')) | (Some('0'), Some('B')) => 2,
+			(Some('0'), Some('o')) | (Some('0'), Some('O')) => 8,
+			_ => 10,
+		};
+		if radix != 10 {
+			return self.lex_prefixed_number(radix, start);
+		}
+		let mut number = Number::new();
+		let mut state = NumberState::new();
+		let mut error = None;
+		while let Some(next) = self.peek() {
+			match next {
+				'0'..='9' => {
+					self.bump();
+					let part = if state.decimal {
+						number.decimal.get_or_insert_with(String::new)
+					} else if state.exponent {
+						number.exponent.get_or_insert_with(String::new)
 					} else {
-						finished = true;
-					}
-					if finished {
-						match identifier.as_str() {
-							"true" => output_stack.push(TokenValue::new(
-								Token::Literal(LiteralValue::Bool(true)),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"false" => output_stack.push(TokenValue::new(
-								Token::Literal(LiteralValue::Bool(false)),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"and" => output_stack.push(TokenValue::new(
-								Token::Operator(Operator::And),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"or" => output_stack.push(TokenValue::new(
-								Token::Operator(Operator::Or),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"not" => output_stack.push(TokenValue::new(
-								Token::Operator(Operator::Not),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"if" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::If),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"endif" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::EndIf),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"elseif" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::ElseIf),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"function" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::Function),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"endfunction" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::EndFunction),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"return" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::EndIf),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"while" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::While),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"endwhile" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::EndWhile),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"for" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::For),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"endfor" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::EndFor),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"to" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::Do),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"do" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::Do),
-								loc.line_num,
-								loc.column_num,
-							)),
-							"then" => output_stack.push(TokenValue::new(
-								Token::Keyword(Keyword::Then),
-								loc.line_num,
-								loc.column_num,
-							)),
-							_ => output_stack.push(TokenValue::new(
-								Token::Identifier(String::from(&identifier)),
-								loc.line_num,
-								loc.column_num,
-							)),
-						}
+						&mut number.base
+					};
+					part.push(next);
+				}
+				'.' if !state.decimal && !state.exponent => {
+					let loc = self.loc;
+					self.bump();
+					if !matches!(self.peek(), Some('0'..='9')) {
+						error.get_or_insert(LexError::MalformedNumber {
+							reason: String::from("no digit after the '.'"),
+							loc,
+						});
 					}
+					state.set_dec(true);
 				}
-			}
-			// match number
-			'0'..'9' => {
-				let mut finished = false;
-				let mut number = Number::new();
-				let mut state = NumberState::new();
-				let mut new_line = false;
-				while !finished {
-					if input_stack.len() > 0 {
-						top = get_next(&mut input_stack);
-						loc.column_num += 1;
-						match top {
-							'0'..'9' => {
-								if state.decimal {
-									number.decimal = match number.decimal {
-										Some(dec) => Some(String::from(dec + &top.to_string())),
-										None => Some(String::from(&top.to_string())),
-									};
-								} else if state.exponent {
-									number.exponent = match number.exponent {
-										Some(exp) => Some(String::from(exp + &top.to_string())),
-										None => Some(String::from(&top.to_string())),
-									}
-								} else {
-									number.base = String::from(number.base) + &top.to_string()
-								}
-							}
-							'.' => {
-								match input_stack.chars().next().unwrap() {
-									'0'..='9' => {}
-									_ => panic!(
-										"No digit after the '.' on line {}, column {}.",
-										loc.line_num, loc.column_num
-									),
-								};
-								state.set_dec(true);
-							}
-							'e' => {
-								match input_stack.chars().next().unwrap() {
-									'0'..='9' => {}
-									_ => panic!(
-										"No digit after the 'e' on line {}, column {}.",
-										loc.line_num, loc.column_num
-									),
-								};
-								state.set_exp(true);
-							}
-							' ' => {
-								break;
-							}
-							'\n' => {
-								loc.line_num += 1;
-								finished = true;
-								new_line = true;
-							}
-							_ => panic!(
-								"Unexpected token {} on line {}, column {}.",
-								top, loc.column_num, loc.line_num
-							),
+				'e' | 'E' if !state.exponent => {
+					let loc = self.loc;
+					self.bump();
+					state.set_exp(true);
+					match self.peek() {
+						Some('-') => {
+							self.bump();
+							number.exponent = Some(String::from("-"));
 						}
-					} else {
-						finished = true;
-					}
-					if finished {
-						output_stack.push(TokenValue::new(
-							Token::Literal(LiteralValue::Number(Number::from_values(
-								&number.exponent,
-								&number.decimal,
-								&number.base,
-							))),
-							loc.line_num,
-							loc.column_num,
-						));
-					}
-					if new_line {
-						output_stack.push(TokenValue::new(
-							Token::NewLine,
-							loc.line_num,
-							loc.column_num,
-						));
+						Some('+') => {
+							self.bump();
+						}
+						_ => {}
 					}
-				}
-			}
-			// match a string
-			'"' => {}
-			'\n' => {
-				output_stack.push(TokenValue::new(Token::NewLine, loc.line_num, loc.column_num));
-				loc.line_num += 1;
-				get_next(&mut input_stack);
-			}
-			'/' => {
-				get_next(&mut input_stack);
-				let next = input_stack.chars().next().unwrap();
-				match next {
-					'/' => {
-						get_next(&mut input_stack);
-						output_stack.push(TokenValue::new(
-							Token::Operator(Operator::IntegerDivide),
-							loc.line_num,
-							loc.column_num,
-						))
+					if !matches!(self.peek(), Some('0'..='9')) {
+						error.get_or_insert(LexError::MalformedNumber {
+							reason: format!("no digit after the '{}'", next),
+							loc,
+						});
 					}
-					_ => output_stack.push(TokenValue::new(
-						Token::Operator(Operator::Divide),
-						loc.line_num,
-						loc.column_num,
-					)),
 				}
+				_ => break,
 			}
-			'+' => {
-				get_next(&mut input_stack);
-				loc.column_num += 1;
-				output_stack.push(TokenValue::new(
-					Token::Operator(Operator::Plus),
-					loc.line_num,
-					loc.column_num,
-				))
-			}
-			'%' => {
-				get_next(&mut input_stack);
-				loc.column_num += 1;
-				output_stack.push(TokenValue::new(
-					Token::Operator(Operator::Modulo),
-					loc.line_num,
-					loc.column_num,
-				))
+		}
+		self.push_number(number, error, start);
+	}
+	/// Lexes a number written in hexadecimal, binary or octal, such as `0x1F`.
+	fn lex_prefixed_number(&mut self, radix: u32, start: (usize, Loc)) {
+		self.bump();
+		self.bump();
+		let mut number = Number::new();
+		number.radix = radix;
+		let mut error = None;
+		// Letters and digits are all consumed, so that a digit which is out of range for the radix
+		// is reported rather than starting a new token.
+		while let Some(next) = self.peek() {
+			if !next.is_ascii_alphanumeric() {
+				break;
 			}
-			'-' => {
-				get_next(&mut input_stack);
-				loc.column_num += 1;
-				output_stack.push(TokenValue::new(
-					Token::Operator(Operator::Minus),
-					loc.line_num,
-					loc.column_num,
-				))
+			if next.is_digit(radix) {
+				number.base.push(next);
+			} else {
+				let loc = self.loc;
+				error.get_or_insert(LexError::MalformedNumber {
+					reason: format!("'{}' is not a valid {} digit", next, radix_name(radix)),
+					loc,
+				});
 			}
-			'*' => {
-				get_next(&mut input_stack);
-				loc.column_num += 1;
-				output_stack.push(TokenValue::new(
-					Token::Operator(Operator::Times),
-					loc.line_num,
-					loc.column_num,
-				))
+			self.bump();
+		}
+		if number.base.is_empty() {
+			error.get_or_insert(LexError::MalformedNumber {
+				reason: format!("no digits after the '{}'", &self.input[start.0..start.0 + 2]),
+				loc: start.1,
+			});
+		}
+		self.push_number(number, error, start);
+	}
+	/// Emits a number, or the first problem found in it (in which case the whole of the number is
+	/// treated as erroneous).
+	fn push_number(&mut self, number: Number, error: Option<LexError>, start: (usize, Loc)) {
+		match error {
+			Some(error) => self.push_error(error, start),
+			None => self.push_token(Token::Literal(LiteralValue::Number(number)), start),
+		}
+	}
+	fn lex_string(&mut self, start: (usize, Loc)) {
+		self.bump();
+		let mut string = String::new();
+		let mut terminated = false;
+		let mut error = None;
+		while let Some(next) = self.peek() {
+			if next == '\n' {
+				break;
 			}
-			'(' => {
-				get_next(&mut input_stack);
-				loc.column_num += 1;
-				output_stack.push(TokenValue::new(Token::OpenBracket, loc.line_num, loc.column_num))
+			self.bump();
+			match next {
+				'"' => {
+					terminated = true;
+					break;
+				}
+				'\\' => {
+					let escape_loc = self.loc;
+					let escaped = match self.peek() {
+						Some('\n') | None => break,
+						Some(escaped) => escaped,
+					};
+					self.bump();
+					match escaped {
+						'"' => string.push('"'),
+						'\'' => string.push('\''),
+						'\\' => string.push('\\'),
+						'n' => string.push('\n'),
+						't' => string.push('\t'),
+						_ => {
+							error.get_or_insert(LexError::UnknownEscape {
+								found: escaped,
+								loc: escape_loc,
+							});
+						}
+					}
+				}
+				_ => string.push(next),
 			}
-			')' => {
-				get_next(&mut input_stack);
-				loc.column_num += 1;
-				output_stack.push(TokenValue::new(
-					Token::CloseBracket,
-					loc.line_num,
-					loc.column_num,
-				))
+		}
+		if !terminated {
+			self.push_error(LexError::UnterminatedString { loc: start.1 }, start);
+		} else if let Some(error) = error {
+			self.push_error(error, start);
+		} else {
+			self.push_token(Token::Literal(LiteralValue::String(string)), start);
+		}
+	}
+	/// Lexes a comment, which runs from a '//' to the end of the line.
+	fn lex_comment(&mut self, start: (usize, Loc)) {
+		self.bump();
+		self.bump();
+		while let Some(next) = self.peek() {
+			if next == '\n' {
+				break;
 			}
-			' ' => {
-				get_next(&mut input_stack);
+			self.bump();
+		}
+		if self.lossless {
+			self.push_trivia(TriviaKind::Comment, start);
+		} else {
+			let comment = String::from(&self.input[start.0 + 2..self.offset()]);
+			self.push_token(Token::Comment(comment), start);
+		}
+	}
+	fn lex_line_continuation(&mut self, start: (usize, Loc)) {
+		self.bump();
+		loop {
+			match self.peek() {
+				Some('\n') => {
+					self.bump();
+					self.push_trivia(TriviaKind::LineContinuation, start);
+					break;
+				}
+				Some(' ') => {
+					self.bump();
+				}
+				_ => {
+					self.push_error(LexError::BadLineContinuation { loc: start.1 }, start);
+					break;
+				}
 			}
-			',' => {
-				get_next(&mut input_stack);
-				output_stack.push(TokenValue::new(Token::Comma, loc.line_num, loc.column_num))
+		}
+	}
+	/// Measures the indentation at the start of a line, producing `Indent` and `Dedent` tokens as
+	/// its width rises above or falls below that of the enclosing lines.
+	fn lex_indentation(&mut self, start: (usize, Loc)) {
+		let (mut tabs, mut spaces) = (false, false);
+		while let Some(next) = self.peek() {
+			match next {
+				' ' => spaces = true,
+				'\t' => tabs = true,
+				_ => break,
 			}
-			'.' => {
-				get_next(&mut input_stack);
-				output_stack.push(TokenValue::new(Token::Dot, loc.line_num, loc.column_num))
+			self.bump();
+		}
+		let width = indentation_width(&self.input[start.0..self.offset()], self.tab_width);
+		// Blank lines and lines containing only a comment do not affect the indentation.
+		if matches!(self.peek(), None | Some('\n')) || self.input[self.offset()..].starts_with("//")
+		{
+			if width > 0 {
+				self.push_trivia(TriviaKind::Whitespace, start);
 			}
-			'=' => {
-				get_next(&mut input_stack);
-				let double_equals = input_stack.chars().next().unwrap();
-				match double_equals {
-					'=' => {
-						get_next(&mut input_stack);
-						loc.column_num += 2;
-						output_stack.push(TokenValue::new(
-							Token::Operator(Operator::DoubleEquals),
-							loc.line_num,
-							loc.column_num,
-						))
-					}
-					_ => {
-						loc.column_num += 1;
-						output_stack.push(TokenValue::new(
-							Token::Operator(Operator::Equals),
-							loc.line_num,
-							loc.column_num,
-						))
-					}
+			return;
+		}
+		let mixed = (tabs && (spaces || self.indent_char == Some(' ')))
+			|| (spaces && self.indent_char == Some('\t'));
+		if !mixed && width > 0 && self.indent_char.is_none() {
+			self.indent_char = Some(if tabs { '\t' } else { ' ' });
+		}
+		let end = self.position();
+		if mixed {
+			self.push_error(LexError::InconsistentIndentation { loc: start.1 }, start);
+		}
+		if width > *self.indent_stack.last().unwrap() {
+			self.indent_stack.push(width);
+			self.push_token(Token::Indent, if mixed { end } else { start });
+			return;
+		}
+		if width > 0 && !mixed {
+			self.push_trivia(TriviaKind::Whitespace, start);
+		}
+		while width < *self.indent_stack.last().unwrap() {
+			self.indent_stack.pop();
+			self.push_token(Token::Dedent, end);
+		}
+		if self.strict_indentation && width > *self.indent_stack.last().unwrap() {
+			self.push_error(LexError::BadDedent { loc: end.1 }, end);
+		}
+	}
+	/// Lexes the next token (or error) from the input into `pending`.
+	fn lex_next(&mut self) {
+		let start = self.position();
+		if self.at_line_start {
+			self.at_line_start = false;
+			self.lex_indentation(start);
+			return;
+		}
+		let top = match self.peek() {
+			Some(top) => top,
+			None => {
+				// The last line is ended before the blocks it is in are closed, even if the input
+				// does not end with a new line, so that `Dedent`s always start a line.
+				if self.indent_stack.len() > 1 && !self.line_ended {
+					self.push_token(Token::NewLine, start);
 				}
-			}
-			'!' => {
-				get_next(&mut input_stack);
-				let next = get_next(&mut input_stack);
-				match next {
-					'=' => {
-						loc.column_num += 1;
-						output_stack.push(TokenValue::new(
-							Token::Operator(Operator::NotEquals),
-							loc.line_num,
-							loc.column_num,
-						))
-					}
-					_ => {
-						panic!(
-                            "Expected an equals following a '!' on line {}, column {}. Note that the 'not' keyword should be used for the 'not' logical operator.",
-                            loc.line_num, loc.column_num
-                        );
-					}
+				while self.indent_stack.len() > 1 {
+					self.indent_stack.pop();
+					self.push_token(Token::Dedent, start);
 				}
-				loc.column_num += 1;
-				output_stack.push(TokenValue::new(
-					Token::Operator(Operator::Equals),
-					loc.line_num,
-					loc.column_num,
-				))
+				self.push_token(Token::EndOfSequence, start);
+				self.finished = true;
+				return;
 			}
-			'>' => {
-				get_next(&mut input_stack);
-				let peek_next = input_stack.chars().next().unwrap();
-				match peek_next {
-					'=' => {
-						get_next(&mut input_stack);
-						loc.column_num += 2;
-						output_stack.push(TokenValue::new(
-							Token::Operator(Operator::GreaterThanOrEqualTo),
-							loc.column_num,
-							loc.line_num,
-						));
-					}
-					_ => {
-						output_stack.push(TokenValue::new(
-							Token::Operator(Operator::GreaterThan),
-							loc.column_num,
-							loc.line_num,
-						));
-					}
+		};
+		match top {
+			_ if is_identifier_start(top) => self.lex_identifier(start),
+			'0'..='9' => self.lex_number(start),
+			'"' => self.lex_string(start),
+			'\\' => self.lex_line_continuation(start),
+			' ' | '\t' => {
+				while let Some(' ') | Some('\t') = self.peek() {
+					self.bump();
 				}
+				self.push_trivia(TriviaKind::Whitespace, start);
 			}
-			'<' => {
-				get_next(&mut input_stack);
-				let peek_next = input_stack.chars().next().unwrap();
-				match peek_next {
-					'=' => {
-						get_next(&mut input_stack);
-						loc.column_num += 2;
-						output_stack.push(TokenValue::new(
-							Token::Operator(Operator::LessThanOrEqualTo),
-							loc.line_num,
-							loc.column_num,
-						));
-					}
-					_ => {
-						output_stack.push(TokenValue::new(
-							Token::Operator(Operator::LessThan),
-							loc.line_num,
-							loc.column_num,
-						));
-					}
+			'\n' => {
+				self.single(Token::NewLine, start);
+				self.at_line_start = true;
+			}
+			'+' => self.single(Token::Operator(Operator::Plus), start),
+			'%' => self.single(Token::Operator(Operator::Modulo), start),
+			'-' => self.single(Token::Operator(Operator::Minus), start),
+			'*' => self.single(Token::Operator(Operator::Times), start),
+			'(' => self.single(Token::OpenBracket, start),
+			')' => self.single(Token::CloseBracket, start),
+			'[' => self.single(Token::OpenSquareBracket, start),
+			']' => self.single(Token::CloseSquareBracket, start),
+			':' => self.single(Token::Colon, start),
+			',' => self.single(Token::Comma, start),
+			'≠' => self.single(Token::Operator(Operator::NotEquals), start),
+			'≤' => self.single(Token::Operator(Operator::LessThanOrEqualTo), start),
+			'≥' => self.single(Token::Operator(Operator::GreaterThanOrEqualTo), start),
+			'.' => self.single(Token::Dot, start),
+			'^' => self.single(Token::Operator(Operator::Exponent), start),
+			'/' => {
+				if self.input[start.0..].starts_with("//") {
+					self.lex_comment(start)
+				} else {
+					self.single(Token::Operator(Operator::Divide), start)
 				}
 			}
-			'\t' => {
-				get_next(&mut input_stack);
-				output_stack.push(TokenValue::new(Token::Tab, loc.line_num, loc.column_num));
+			'=' => self.single_or_double(
+				'=',
+				Token::Operator(Operator::DoubleEquals),
+				Token::Operator(Operator::Equals),
+				start,
+			),
+			'>' => self.single_or_double(
+				'=',
+				Token::Operator(Operator::GreaterThanOrEqualTo),
+				Token::Operator(Operator::GreaterThan),
+				start,
+			),
+			'<' => {
+				self.bump();
+				let token = match self.peek() {
+					Some('=') => Token::Operator(Operator::LessThanOrEqualTo),
+					// '<>' is used for "not equal to" in the AQA and Cambridge guides.
+					Some('>') => Token::Operator(Operator::NotEquals),
+					_ => return self.push_token(Token::Operator(Operator::LessThan), start),
+				};
+				self.bump();
+				self.push_token(token, start);
 			}
-			'\\' => {
-				get_next(&mut input_stack);
-				loop {
-					let peek_next = input_stack.chars().next().unwrap();
-					match peek_next {
-						'\n' => {
-							get_next(&mut input_stack);
-							break;
-						}
-						' ' => {}
-						_ => panic!(
-							"Expected a new line (or space) following the backslash on line {}, \
-							 column {}.",
-							loc.line_num, loc.column_num
-						),
-					}
+			'!' => {
+				self.bump();
+				if self.peek() == Some('=') {
+					self.bump();
+					self.push_token(Token::Operator(Operator::NotEquals), start);
+				} else {
+					self.push_error(LexError::ExpectedEquals { loc: start.1 }, start);
 				}
 			}
-			_ => panic!(
-				"Found an invalid token {} at line {}, column {}.",
-				top, loc.line_num, loc.column_num
-			),
-		};
+			_ => {
+				self.bump();
+				self.push_error(LexError::UnexpectedChar { found: top, loc: start.1 }, start);
+			}
+		}
+	}
+}
+
+impl<'a> Iterator for Lexer<'a> {
+	type Item = Result<TokenValue, LexError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		while self.pending.is_empty() && !self.finished {
+			self.lex_next();
+		}
+		self.pending.pop_front()
+	}
+}
+
+/// Runs a lexical analysis procedure, returning a list of token values which can be used for further processing.
+///
+/// If the input is malformed, lexing continues past each problem so that every error in the input
+/// is returned, rather than just the first one.
+pub fn lexer(input: &str) -> Result<Vec<TokenValue>, Vec<LexError>> {
+	lexer_with_config(input, LexerConfig::default())
+}
+
+/// Runs the lexer over the input in the same way as `lexer`, but with the given configuration.
+pub fn lexer_with_config(
+	input: &str,
+	config: LexerConfig,
+) -> Result<Vec<TokenValue>, Vec<LexError>> {
+	let mut tokens = Vec::new();
+	let mut errors = Vec::new();
+	for item in Lexer::with_config(input, config) {
+		match item {
+			Ok(token) => tokens.push(token),
+			Err(error) => errors.push(error),
+		}
+	}
+	if errors.is_empty() {
+		Ok(tokens)
+	} else {
+		Err(errors)
 	}
-	output_stack.push(TokenValue::new(Token::EndOfSequence, loc.line_num, loc.column_num));
-	output_stack
 }
@@ -1,3 +1,6 @@
 pub mod helpers;
+pub mod highlight;
+pub mod incremental;
+pub mod keywords;
 pub mod lexer;
 pub mod parser;
@@ -1,24 +1,63 @@
 use crate::parser::lexer::{Keyword, LiteralValue, Loc, Number, Operator, Token, TokenValue};
 use indextree::{Arena, NodeId};
+use std::collections::VecDeque;
 
 #[derive(Debug)]
 #[derive(Clone)]
 #[derive(PartialEq)]
 pub enum Item {
+	// The children are the name of the function (an `Identifier`), its `ParameterList` and its body.
 	Function,
-	ParameterList(Vec<Item>),
-	// The `String` is the name of the function being called.
+	// The children are the same as those of a `Function`.
+	Procedure,
+	// The child (if a value is returned) is the `Expression` being returned.
+	Return,
+	// Each parameter is its name (an `Identifier`) and how arguments are passed to it.
+	ParameterList(Vec<(Item, Passing)>),
+	// The `String` is the name of the function being called, and the children are the arguments.
 	Call(String),
+	// The children are the target (an `Identifier` or an `Index`) and the `Expression` assigned to it.
 	Assign,
 	Operator(Operator),
+	// The children are the condition (an `Expression`), the block run if it is true, any number of
+	// `ElseIf` branches and then, if there is an `else` branch, its block.
 	If,
+	// The children are the condition and the block run if it is true.
 	ElseIf,
 	// The `String` is the identifier name
 	Identifier(String),
 	Expression,
 	Number(Number),
+	String(String),
+	Bool(bool),
+	// The children are the array being indexed and then the indices.
+	Index,
+	// The children are the elements of the list.
+	List,
+	// The declaration of an array. The children are its name (an `Identifier`) and then the size of
+	// each of its dimensions.
+	Array,
+	// The children are the condition and the body of the loop.
 	While,
+	// The children are the body of the loop and the condition which ends it (which is checked after
+	// each time the body is run).
+	DoUntil,
+	// The children of a count-controlled loop are the loop variable (an `Identifier`), the `Start`,
+	// `End` and `Step` of the count (each containing an expression) and the body.
 	For,
+	Start,
+	End,
+	Step,
+	// The children of a loop over the elements of an array (or the characters of a string) are the
+	// loop variable (an `Identifier`), the collection (an `Expression`) and the body.
+	ForIn,
+	// The children are the value being switched on (an `Expression`), any number of `Case` arms and
+	// then (if there is one) the `Default` arm.
+	Switch,
+	// The children are the values which select the arm, followed by its block.
+	Case,
+	// The child is the block run if no case matches.
+	Default,
 	Body,
 	Program,
 	Block,
@@ -28,11 +67,20 @@ pub fn priority(o: &TokenValue) -> u32 {
 	match &o.token {
 		Token::Operator(o) => {
 			match o {
+				Operator::Exponent => 7,
+				Operator::Negate => 6,
 				Operator::Times | Operator::Divide | Operator::IntegerDivide | Operator::Modulo => {
 					5
 				}
 				Operator::Plus | Operator::Minus => 4,
-				Operator::Equals | Operator::NotEquals => 3,
+				Operator::Equals
+				| Operator::DoubleEquals
+				| Operator::NotEquals
+				| Operator::GreaterThan
+				| Operator::LessThan
+				| Operator::GreaterThanOrEqualTo
+				| Operator::LessThanOrEqualTo
+				| Operator::In => 3,
 				Operator::And => 2,
 				Operator::Or => 1,
 				Operator::Not => 5,
@@ -59,6 +107,8 @@ pub fn left_associative(o: &TokenValue) -> bool {
 				Operator::And => true,
 				Operator::Or => true,
 				Operator::Not => false,
+				Operator::Negate => false,
+				Operator::Exponent => false,
 				// This is kept here in case more operators are to be added
 				_ => true,
 			}
@@ -67,17 +117,24 @@ pub fn left_associative(o: &TokenValue) -> bool {
 	}
 }
 
+/// Whether the operator comes before its only operand (rather than between two operands).
+pub fn is_prefix(o: &TokenValue) -> bool {
+	matches!(o.token, Token::Operator(Operator::Not) | Token::Operator(Operator::Negate))
+}
+
 pub fn is_operator(o: &TokenValue) -> bool {
 	match o.token {
-		Token::FunctionCall(_) | Token::Operator(_) => true,
+		Token::FunctionCall(_) | Token::OpenSquareBracket | Token::Operator(_) => true,
 		_ => false,
 	}
 }
 
-fn get_next_token(tokens: &mut Vec<TokenValue>) -> TokenValue {
-	let result = tokens.first().unwrap().clone();
-	tokens.remove(0);
-	result
+/// How an argument is passed to a parameter. Parameters are passed by value unless they are
+/// declared with `:byRef`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Passing {
+	ByVal,
+	ByRef,
 }
 
 #[derive(Debug)]
@@ -93,207 +150,490 @@ impl Node {
 	}
 }
 
-fn parse_expression(
-	indentation: i32,
-	parent: &NodeId,
-	arena: &mut Arena<Node>,
-	tokens: &mut Vec<TokenValue>,
-) {
+/// A problem with a program which does not stop it from being compiled, such as indentation which
+/// does not match the structure of the blocks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+	pub loc: Loc,
+	pub message: String,
+}
+
+/// The tokens of the program which are still to be parsed, which are taken from the front as they
+/// are parsed. The last token is always an `EndOfSequence`.
+struct Tokens {
+	tokens: VecDeque<TokenValue>,
+	/// The number of `Indent`s taken so far which have not been closed by a `Dedent`.
+	depth: i32,
+	warnings: Vec<Warning>,
+}
+
+impl Tokens {
+	/// The next token, which is left in place.
+	fn peek(&self) -> &TokenValue {
+		&self.tokens[0]
+	}
+	/// Takes the next token.
+	fn pop(&mut self) -> TokenValue {
+		let token = self.tokens.pop_front().unwrap();
+		match token.token {
+			Token::Indent => self.depth += 1,
+			Token::Dedent => self.depth -= 1,
+			_ => {}
+		}
+		token
+	}
+	/// Takes the new lines and changes of indentation before the next statement.
+	fn skip_layout(&mut self) {
+		while let Token::NewLine | Token::Indent | Token::Dedent = self.peek().token {
+			self.pop();
+		}
+	}
+	/// Records a warning if the line starting with `token` is not indented by `indentation` levels.
+	/// Blocks are delimited by their keywords, so this does not change how the program is parsed.
+	fn check_indentation(&mut self, indentation: i32, token: &TokenValue) {
+		if self.depth != indentation && token.token != Token::EndOfSequence {
+			let loc = token.span.start_loc;
+			self.warnings.push(Warning {
+				loc,
+				message: format!(
+					"Misleading indentation on line {}, column {}. Expected {} level{} of \
+					 indentation, but found {}.",
+					loc.line_num,
+					loc.column_num,
+					indentation,
+					if indentation == 1 { "" } else { "s" },
+					self.depth
+				),
+			});
+		}
+	}
+}
+
+/// The kinds of bracketed, comma separated lists found in expressions (apart from the parameter lists
+/// of declarations).
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Group {
+	/// The arguments of a call, such as `f(a, b)`.
+	Call,
+	/// The indices of an element of an array, such as `board[x, y]`.
+	Index,
+	/// The elements of a list literal, such as `[1, 2, 3]`.
+	List,
+}
+
+fn parse_expression(parent: &NodeId, arena: &mut Arena<Node>, tokens: &mut VecDeque<TokenValue>) {
 	let mut output = Vec::new();
 	let mut op_stack = Vec::new();
+	// The kind and the number of items found so far of each group on the operator stack (calls
+	// and '[' tokens), innermost last.
+	let mut groups: Vec<(Group, usize)> = Vec::new();
+	// The kind and size of each group in the output, in the order they were output.
+	let mut output_groups: Vec<(Group, usize)> = Vec::new();
+	// Whether the next token should start an operand (so a '-' is a unary minus).
+	let mut expect_operand = true;
 	loop {
-		let next = tokens.remove(0);
+		let mut next = tokens.pop_front().unwrap();
 		match &next.token {
-			Token::Identifier(s) => {
-				let look_ahead = tokens.iter().next().unwrap();
-				match look_ahead.token {
-					Token::OpenBracket => op_stack.push(next),
-					_ => output.push(next),
+			Token::Identifier(s) => match tokens[0].token {
+				Token::OpenBracket => {
+					tokens.pop_front().unwrap();
+					let empty = tokens[0].token == Token::CloseBracket;
+					groups.push((Group::Call, if empty { 0 } else { 1 }));
+					op_stack.push(TokenValue::new(Token::FunctionCall(String::from(s)), next.span));
+					expect_operand = true;
 				}
-			}
-			Token::Literal(LiteralValue::Number(_)) => {
+				_ => {
+					output.push(next);
+					expect_operand = false;
+				}
+			},
+			Token::Literal(_) => {
 				output.push(next);
+				expect_operand = false;
+			}
+			Token::Operator(operator) if expect_operand => {
+				match operator {
+					Operator::Minus => next.token = Token::Operator(Operator::Negate),
+					Operator::Not => {}
+					_ => panic!(
+						"Missing an operand in the expression on line {}, column {}",
+						next.span.start_loc.line_num, next.span.start_loc.column_num
+					),
+				}
+				// Nothing before a prefix operator can be its operand, so nothing is output.
+				op_stack.push(next);
 			}
+			Token::Operator(_) if is_prefix(&next) => panic!(
+				"Invalid token in the expression on line {}, column {}",
+				next.span.start_loc.line_num, next.span.start_loc.column_num
+			),
 			Token::Operator(_) => {
-				if priority(&next) > priority(op_stack.iter().next().unwrap()) {
-					op_stack.push(next);
-				} else {
-					loop {
-						let next_operator = op_stack.remove(0);
-						if priority(&next_operator) >= priority(&next) {
-							output.push(next_operator);
-						} else {
-							op_stack.push(next);
-							break;
+				expect_operand = true;
+				while let Some(top) = op_stack.last() {
+					let binds_tighter = match top.token {
+						Token::Operator(_) => {
+							priority(top) > priority(&next)
+								|| (priority(top) == priority(&next) && left_associative(&next))
 						}
+						_ => false,
+					};
+					if !binds_tighter {
+						break;
 					}
+					output.push(op_stack.pop().unwrap());
 				}
+				op_stack.push(next);
 			}
-			Token::OpenBracket => op_stack.push(next),
-			Token::CloseBracket => {
-				while op_stack.get(0).unwrap().token != Token::CloseBracket {
-					output.push(match op_stack.get(0).unwrap().token {
-						Token::EndOfSequence => panic!(
-							"Missing a closing bracket on line {}, column {}",
-							op_stack.get(0).unwrap().loc.line_num,
-							op_stack.get(0).unwrap().loc.column_num
-						),
-						_ => op_stack.remove(0),
-					})
+			Token::OpenBracket => {
+				op_stack.push(next);
+				expect_operand = true;
+			}
+			// A '[' after an operand indexes it, and anywhere else starts a list.
+			Token::OpenSquareBracket => {
+				if expect_operand {
+					let empty = tokens[0].token == Token::CloseSquareBracket;
+					groups.push((Group::List, if empty { 0 } else { 1 }));
+				} else {
+					groups.push((Group::Index, 1));
 				}
-				op_stack.pop();
+				op_stack.push(next);
+				expect_operand = true;
 			}
+			Token::CloseSquareBracket => loop {
+				expect_operand = false;
+				match op_stack.pop() {
+					Some(group @ TokenValue { token: Token::OpenSquareBracket, .. }) => {
+						output_groups.push(groups.pop().unwrap());
+						output.push(group);
+						break;
+					}
+					Some(TokenValue { token: Token::OpenBracket, .. })
+					| Some(TokenValue { token: Token::FunctionCall(_), .. })
+					| None => panic!(
+						"Unexpected closing square bracket on line {}, column {}",
+						next.span.start_loc.line_num, next.span.start_loc.column_num
+					),
+					Some(operator) => output.push(operator),
+				}
+			},
+			// A comma ends an argument, so everything since the start of it is output.
+			Token::Comma => loop {
+				expect_operand = true;
+				match op_stack.last().map(|top| &top.token) {
+					Some(Token::FunctionCall(_)) | Some(Token::OpenSquareBracket) => {
+						groups.last_mut().unwrap().1 += 1;
+						break;
+					}
+					Some(Token::OpenBracket) | None => panic!(
+						"Unexpected comma on line {}, column {}",
+						next.span.start_loc.line_num, next.span.start_loc.column_num
+					),
+					Some(_) => output.push(op_stack.pop().unwrap()),
+				}
+			},
+			Token::CloseBracket => loop {
+				expect_operand = false;
+				match op_stack.pop() {
+					Some(TokenValue { token: Token::OpenBracket, .. }) => break,
+					Some(call @ TokenValue { token: Token::FunctionCall(_), .. }) => {
+						output_groups.push(groups.pop().unwrap());
+						output.push(call);
+						break;
+					}
+					Some(TokenValue { token: Token::OpenSquareBracket, .. }) | None => panic!(
+						"Unexpected closing bracket on line {}, column {}",
+						next.span.start_loc.line_num, next.span.start_loc.column_num
+					),
+					Some(operator) => output.push(operator),
+				}
+			},
 			Token::EndOfSequence => {
-				for i in 1..op_stack.clone().iter().count() {
-					output.push(op_stack.remove(0))
+				while let Some(operator) = op_stack.pop() {
+					match operator.token {
+						Token::OpenBracket | Token::OpenSquareBracket | Token::FunctionCall(_) => {
+							panic!(
+								"Missing a closing bracket on line {}, column {}",
+								operator.span.start_loc.line_num,
+								operator.span.start_loc.column_num
+							)
+						}
+						_ => output.push(operator),
+					}
 				}
 				break;
 			}
 			_ => panic!(
 				"Invalid token in the expression on line {}, column {}",
-				next.loc.line_num, next.loc.column_num
+				next.span.start_loc.line_num, next.span.start_loc.column_num
 			),
 		}
 	}
 	let mut stack: Vec<NodeId> = Vec::new();
-	let mut last_operator = 0;
-	for (pos, token) in output.clone().iter().enumerate() {
+	let mut output_groups = output_groups.into_iter();
+	for token in output {
+		let missing_operand = || {
+			panic!(
+				"Missing an operand in the expression on line {}, column {}",
+				token.span.start_loc.line_num, token.span.start_loc.column_num
+			)
+		};
 		if !is_operator(&token) {
 			stack.push(arena.new_node(Node::new(
 				match &token.token {
 					Token::Identifier(s) => Item::Identifier(String::from(s)),
 					Token::Literal(LiteralValue::Number(n)) => Item::Number(n.clone()),
+					Token::Literal(LiteralValue::String(s)) => Item::String(String::from(s)),
+					Token::Literal(LiteralValue::Bool(b)) => Item::Bool(*b),
 					_ => panic!(
 						"Invalid token in the expression on line {}, column {}",
-						token.loc.line_num, token.loc.column_num
+						token.span.start_loc.line_num, token.span.start_loc.column_num
 					),
 				},
-				token.loc,
+				token.span.start_loc,
 			)));
-			last_operator += 1;
 		} else {
 			match &token.token {
-				Token::FunctionCall(s) => {
-					let function_node =
-						arena.new_node(Node::new(Item::Call(String::from(s)), token.loc));
-					for i in 1..last_operator {
-						function_node.append(stack.pop().unwrap(), arena)
+				Token::FunctionCall(_) | Token::OpenSquareBracket => {
+					let (group, mut count) = output_groups.next().unwrap();
+					let item = match (&token.token, group) {
+						(Token::FunctionCall(s), _) => Item::Call(String::from(s)),
+						// The array being indexed comes before the indices.
+						(_, Group::Index) => {
+							if count > 2 {
+								panic!(
+									"Arrays can have at most two dimensions, but the index on line \
+									 {}, column {} has {}.",
+									token.span.start_loc.line_num,
+									token.span.start_loc.column_num,
+									count
+								);
+							}
+							count += 1;
+							Item::Index
+						}
+						_ => Item::List,
+					};
+					let group_node = arena.new_node(Node::new(item, token.span.start_loc));
+					if count > stack.len() {
+						missing_operand();
+					}
+					for child in stack.split_off(stack.len() - count) {
+						group_node.append(child, arena);
 					}
-					stack.push(function_node);
+					stack.push(group_node);
+				}
+				Token::Operator(o) if is_prefix(&token) => {
+					let operand = stack.pop().unwrap_or_else(missing_operand);
+					let operator_node =
+						arena.new_node(Node::new(Item::Operator(o.clone()), token.span.start_loc));
+					operator_node.append(operand, arena);
+					stack.push(operator_node);
 				}
 				Token::Operator(o) => {
-					let o1 = stack.pop().unwrap();
-					let o2 = stack.pop().unwrap();
+					let right = stack.pop().unwrap_or_else(missing_operand);
+					let left = stack.pop().unwrap_or_else(missing_operand);
 					let operator_node =
-						arena.new_node(Node::new(Item::Operator(o.clone()), token.loc));
-					operator_node.append(o1, arena);
-					operator_node.append(o2, arena);
+						arena.new_node(Node::new(Item::Operator(o.clone()), token.span.start_loc));
+					operator_node.append(left, arena);
+					operator_node.append(right, arena);
 					stack.push(operator_node);
 				}
 				_ => panic!(
 					"Invalid token in the expression on line {}, column {}",
-					token.loc.line_num, token.loc.column_num
+					token.span.start_loc.line_num, token.span.start_loc.column_num
 				),
 			}
-			last_operator = 0;
 		}
 	}
-	parent.append(stack.pop().unwrap(), arena);
+	match (stack.pop(), stack.pop()) {
+		(Some(root), None) => parent.append(root, arena),
+		(None, _) => panic!("Expected an expression on line {}", arena[*parent].get().loc.line_num),
+		(Some(_), Some(extra)) => {
+			let loc = arena[extra].get().loc;
+			panic!(
+				"Expected an operator in the expression on line {}, column {}",
+				loc.line_num, loc.column_num
+			)
+		}
+	}
 }
 
-fn parse_if(
-	indentation: i32,
-	parent: &NodeId,
-	arena: &mut Arena<Node>,
-	tokens: &mut Vec<TokenValue>,
-) {
-	let mut then_token = false;
-	let mut expression: Vec<TokenValue> = Vec::new();
-	let start_loc = tokens.iter().next().unwrap().clone();
-	while !then_token {
-		let next = tokens.remove(0);
+/// Parses the condition of an `if` or `elseif` statement (up to and including the `then` keyword and
+/// the new line after it) into an `Expression` node appended to `parent`.
+fn parse_condition(parent: &NodeId, arena: &mut Arena<Node>, tokens: &mut Tokens) {
+	let mut expression = VecDeque::new();
+	let start_loc = tokens.peek().span.start_loc;
+	loop {
+		let next = tokens.pop();
 		match next.token {
 			Token::Keyword(Keyword::Then) => {
-				then_token = true;
-				let new_line = tokens.remove(0);
+				expression.push_back(TokenValue::new(Token::EndOfSequence, next.span));
+				let new_line = tokens.pop();
 				match new_line.token {
 					Token::NewLine => {}
 					_ => {
-						panic!("Expected a new line following the 'then' keyword on line {}, column {}", new_line.loc.line_num, new_line.loc.column_num);
+						panic!("Expected a new line following the 'then' keyword on line {}, column {}", new_line.span.start_loc.line_num, new_line.span.start_loc.column_num);
 					}
 				}
+				break;
 			}
+			Token::NewLine | Token::EndOfSequence => panic!(
+				"Expected the 'then' keyword following the condition on line {}, column {}",
+				next.span.start_loc.line_num, next.span.start_loc.column_num
+			),
 			_ => {
-				expression.push(next);
+				expression.push_back(next);
 			}
 		}
 	}
-	let expression_node = arena.new_node(Node::new(Item::Expression, start_loc.loc));
+	let expression_node = arena.new_node(Node::new(Item::Expression, start_loc));
 	parent.append(expression_node, arena);
-	parse_expression(indentation, &expression_node, arena, &mut expression);
+	parse_expression(&expression_node, arena, &mut expression);
 }
 
-fn parse_while(
-	indentation: i32,
-	parent: &NodeId,
-	arena: &mut Arena<Node>,
-	tokens: &mut Vec<TokenValue>,
-) {
-	let n = arena.new_node(Node::new(Item::While, arena.get(*parent).unwrap().get().loc));
-	parent.append(n, arena);
-	let mut do_token_found = false;
-	let mut expression: Vec<TokenValue> = Vec::new();
-	while !do_token_found {
-		let do_token = tokens.remove(0);
-		match do_token.token {
+fn parse_if(indentation: i32, parent: &NodeId, arena: &mut Arena<Node>, tokens: &mut Tokens) {
+	let if_loc = arena.get(*parent).unwrap().get().loc;
+	parse_condition(parent, arena, tokens);
+	let mut block = arena.new_node(Node::new(Item::Block, tokens.peek().span.start_loc));
+	parent.append(block, arena);
+	let mut terminators = vec![
+		Token::Keyword(Keyword::ElseIf),
+		Token::Keyword(Keyword::Else),
+		Token::Keyword(Keyword::EndIf),
+	];
+	loop {
+		let end = parse_block(indentation + 1, &block, arena, tokens, &terminators);
+		tokens.check_indentation(indentation, &end);
+		match end.token {
+			// The block stops at another branch without taking it if the branch is not allowed.
+			Token::Keyword(Keyword::ElseIf) | Token::Keyword(Keyword::Else)
+				if !terminators.contains(&end.token) =>
+			{
+				panic!(
+					"'else' must be the last branch of the 'if' statement on line {}, but '{}' was \
+					 found on line {}, column {}.",
+					if_loc.line_num,
+					if end.token == Token::Keyword(Keyword::Else) { "else" } else { "elseif" },
+					end.span.start_loc.line_num,
+					end.span.start_loc.column_num
+				)
+			}
+			Token::Keyword(Keyword::ElseIf) => {
+				let else_if = arena.new_node(Node::new(Item::ElseIf, end.span.start_loc));
+				parent.append(else_if, arena);
+				parse_condition(&else_if, arena, tokens);
+				block = arena.new_node(Node::new(Item::Block, tokens.peek().span.start_loc));
+				else_if.append(block, arena);
+			}
+			Token::Keyword(Keyword::Else) => {
+				block = arena.new_node(Node::new(Item::Block, tokens.peek().span.start_loc));
+				parent.append(block, arena);
+				// Nothing but the end of the statement can follow the `else` branch.
+				terminators = vec![Token::Keyword(Keyword::EndIf)];
+			}
+			Token::Keyword(Keyword::EndIf) => break,
+			_ => panic!(
+				"Missing 'endif' for the 'if' statement on line {}, column {}.",
+				if_loc.line_num, if_loc.column_num
+			),
+		}
+	}
+}
+
+fn parse_while(indentation: i32, parent: &NodeId, arena: &mut Arena<Node>, tokens: &mut Tokens) {
+	let while_loc = arena.get(*parent).unwrap().get().loc;
+	let mut expression = VecDeque::new();
+	let start_loc = tokens.peek().span.start_loc;
+	// The condition is ended by the end of the line, optionally preceded by 'do'.
+	loop {
+		let next_token = tokens.pop();
+		match next_token.token {
 			Token::Keyword(Keyword::Do) => {
-				do_token_found = true;
+				expression.push_back(TokenValue::new(Token::EndOfSequence, next_token.span));
+				let new_line = tokens.pop();
+				if new_line.token != Token::NewLine {
+					panic!(
+						"Expected a new line following the 'do' keyword on line {}, column {}.",
+						new_line.span.start_loc.line_num, new_line.span.start_loc.column_num
+					);
+				}
+				break;
+			}
+			Token::NewLine => {
+				expression.push_back(TokenValue::new(Token::EndOfSequence, next_token.span));
+				break;
 			}
 			Token::EndOfSequence => panic!(
-				"Expected a 'do' token following the while loop on line {}, column {}.",
-				do_token.loc.line_num, do_token.loc.column_num
+				"Missing 'endwhile' for the 'while' loop on line {}, column {}.",
+				while_loc.line_num, while_loc.column_num
 			),
-			Token::NewLine => panic!(
-				"Expected a 'do' token following the while loop on line {}, column {}.",
-				do_token.loc.line_num, do_token.loc.column_num
-			),
-			_ => {
-				expression.push(do_token);
-			}
+			_ => expression.push_back(next_token),
 		}
 	}
-	parse_expression(indentation, &n, arena, &mut expression);
-	let end_token = tokens.remove(0);
-	if end_token.token != Token::Keyword(Keyword::EndWhile) {
+	let expression_node = arena.new_node(Node::new(Item::Expression, start_loc));
+	parent.append(expression_node, arena);
+	parse_expression(&expression_node, arena, &mut expression);
+
+	let block = arena.new_node(Node::new(Item::Block, tokens.peek().span.start_loc));
+	parent.append(block, arena);
+	let end =
+		parse_block(indentation + 1, &block, arena, tokens, &[Token::Keyword(Keyword::EndWhile)]);
+	tokens.check_indentation(indentation, &end);
+	if end.token != Token::Keyword(Keyword::EndWhile) {
 		panic!(
-			"Expected the keyword 'endwhile' on line {}, column {}",
-			end_token.loc.line_num, end_token.loc.column_num
-		)
+			"Missing 'endwhile' for the 'while' loop on line {}, column {}.",
+			while_loc.line_num, while_loc.column_num
+		);
 	}
 }
 
-fn parse_for(
-	indentation: i32,
-	parent: &NodeId,
-	arena: &mut Arena<Node>,
-	tokens: &mut Vec<TokenValue>,
-) {
-	let for_node = arena.new_node(Node::new(Item::For, arena.get(*parent).unwrap().get().loc));
-	parent.append(for_node, arena);
+fn parse_do_until(indentation: i32, parent: &NodeId, arena: &mut Arena<Node>, tokens: &mut Tokens) {
+	let do_loc = arena[*parent].get().loc;
+	// Unlike the 'do' which can end the condition of a while loop, this 'do' is on a line of its own.
+	let new_line = tokens.pop();
+	if new_line.token != Token::NewLine {
+		panic!(
+			"Expected a new line following the 'do' keyword on line {}, column {}.",
+			new_line.span.start_loc.line_num, new_line.span.start_loc.column_num
+		);
+	}
+	let block = arena.new_node(Node::new(Item::Block, tokens.peek().span.start_loc));
+	parent.append(block, arena);
+	let end =
+		parse_block(indentation + 1, &block, arena, tokens, &[Token::Keyword(Keyword::Until)]);
+	tokens.check_indentation(indentation, &end);
+	if end.token != Token::Keyword(Keyword::Until) {
+		panic!(
+			"Missing 'until' for the 'do' loop on line {}, column {}.",
+			do_loc.line_num, do_loc.column_num
+		);
+	}
+	let mut condition = take_line(tokens);
+	let expression_node = arena.new_node(Node::new(Item::Expression, condition[0].span.start_loc));
+	parent.append(expression_node, arena);
+	parse_expression(&expression_node, arena, &mut condition);
+}
+
+fn parse_for(indentation: i32, parent: &NodeId, arena: &mut Arena<Node>, tokens: &mut Tokens) {
+	let for_node = *parent;
+	let for_loc = arena[for_node].get().loc;
 
 	let mut count_variable = String::new();
-	let identifier = tokens.remove(0);
+	let identifier = tokens.pop();
 	match identifier.token {
 		Token::Identifier(s) => {
 			count_variable = s;
 		}
 		_ => panic!(
 			"Expected a variable after the keyword 'for' on line {}, column {}",
-			identifier.loc.line_num, identifier.loc.column_num
+			identifier.span.start_loc.line_num, identifier.span.start_loc.column_num
 		),
 	}
+	let variable_node = arena
+		.new_node(Node::new(Item::Identifier(count_variable.clone()), identifier.span.start_loc));
+	for_node.append(variable_node, arena);
 
-	let equals_sign = tokens.remove(0);
+	let equals_sign = tokens.pop();
 	let mut is_count = false;
 	match equals_sign.token {
         Token::Operator(Operator::Equals) => {
@@ -304,149 +644,471 @@ fn parse_for(
         }
         _ => panic!(
             "Expected an equals sign or the 'in' keyword after the variable {} in the for loop on line {}, column {}",
-            count_variable, equals_sign.loc.line_num, equals_sign.loc.column_num
+            count_variable, equals_sign.span.start_loc.line_num, equals_sign.span.start_loc.column_num
         ),
     }
-	if is_count {
-		let mut expression_block_1: Vec<TokenValue> = Vec::new();
-		let mut e_1 = false;
-		while !e_1 {
-			let next_token = tokens.remove(0);
-			match next_token.token {
-				Token::Keyword(Keyword::To) => {
-					e_1 = true;
-				}
-				Token::NewLine | Token::EndOfSequence => panic!(
-					"Expected the 'to' keyword following 'for <variable>=<expression>' on \
-					 line {}, column {}.",
-					next_token.loc.line_num, next_token.loc.column_num
-				),
-				_ => expression_block_1.push(next_token),
+	// The header is ended by the end of the line, optionally preceded by 'do'.
+	let header_end = if is_count {
+		let (mut start, to) = take_expression(tokens, &[Token::Keyword(Keyword::To)]);
+		if to.token != Token::Keyword(Keyword::To) {
+			panic!(
+				"Expected the 'to' keyword following 'for <variable>=<expression>' on line {}, \
+				 column {}.",
+				to.span.start_loc.line_num, to.span.start_loc.column_num
+			);
+		}
+		let (mut end, after_end) =
+			take_expression(tokens, &[Token::Keyword(Keyword::Step), Token::Keyword(Keyword::Do)]);
+		let (mut step, header_end) = match after_end.token {
+			Token::Keyword(Keyword::Step) => {
+				take_expression(tokens, &[Token::Keyword(Keyword::Do)])
 			}
+			// Without a step, the loop counts up in ones.
+			_ => (
+				VecDeque::from(vec![
+					TokenValue::new(
+						Token::Literal(LiteralValue::Number(Number {
+							base: String::from("1"),
+							exponent: None,
+							decimal: None,
+							radix: 10,
+						})),
+						after_end.span,
+					),
+					TokenValue::new(Token::EndOfSequence, after_end.span),
+				]),
+				after_end,
+			),
+		};
+		for (item, expression) in
+			[(Item::Start, &mut start), (Item::End, &mut end), (Item::Step, &mut step)]
+		{
+			let node = arena.new_node(Node::new(item, expression[0].span.start_loc));
+			for_node.append(node, arena);
+			parse_expression(&node, arena, expression);
 		}
-		let mut e_2 = false;
-		let mut expression_block_2: Vec<TokenValue> = Vec::new();
-		while !e_2 {
-			let next_token = tokens.remove(0);
-			match next_token.token {
-				Token::Keyword(Keyword::Do) => {
-					e_2 = true;
+		header_end
+	} else {
+		arena[for_node].get_mut().item = Item::ForIn;
+		let (mut collection, header_end) = take_expression(tokens, &[Token::Keyword(Keyword::Do)]);
+		let expression_node =
+			arena.new_node(Node::new(Item::Expression, collection[0].span.start_loc));
+		for_node.append(expression_node, arena);
+		parse_expression(&expression_node, arena, &mut collection);
+		header_end
+	};
+	if header_end.token == Token::Keyword(Keyword::Do) && tokens.peek().token != Token::NewLine {
+		panic!(
+			"Expected a new line following the 'do' keyword on line {}, column {}.",
+			tokens.peek().span.start_loc.line_num,
+			tokens.peek().span.start_loc.column_num
+		);
+	}
+	if tokens.peek().token == Token::NewLine {
+		tokens.pop();
+	}
+
+	let block = arena.new_node(Node::new(Item::Block, tokens.peek().span.start_loc));
+	for_node.append(block, arena);
+	let terminators = [Token::Keyword(Keyword::Next), Token::Keyword(Keyword::EndFor)];
+	let end_token = parse_block(indentation + 1, &block, arena, tokens, &terminators);
+	tokens.check_indentation(indentation, &end_token);
+	match end_token.token {
+		Token::Keyword(Keyword::Next) => match tokens.peek().token.clone() {
+			Token::Identifier(s) => {
+				let next_variable = tokens.pop();
+				if s != count_variable {
+					panic!(
+						"Expected 'next {}' (to match the loop on line {}), but found 'next \
+						 {}' on line {}, column {}.",
+						count_variable,
+						for_loc.line_num,
+						s,
+						next_variable.span.start_loc.line_num,
+						next_variable.span.start_loc.column_num
+					);
 				}
-				Token::NewLine | Token::EndOfSequence => panic!(
-					"Expected the 'do' keyword following 'for <variable>=<expression> to \
-					 <expression>' on line {}, column {}.",
-					next_token.loc.line_num, next_token.loc.column_num
-				),
-				_ => expression_block_2.push(next_token),
 			}
+			_ => panic!(
+				"Expected the loop variable '{}' after 'next' on line {}, column {}.",
+				count_variable,
+				tokens.peek().span.start_loc.line_num,
+				tokens.peek().span.start_loc.column_num
+			),
+		},
+		Token::Keyword(Keyword::EndFor) => {}
+		_ => panic!(
+			"Missing 'next {}' for the 'for' loop on line {}, column {}.",
+			count_variable, for_loc.line_num, for_loc.column_num
+		),
+	}
+}
+
+fn parse_array(parent: &NodeId, arena: &mut Arena<Node>, tokens: &mut Tokens) {
+	let identifier = tokens.pop();
+	match identifier.token {
+		Token::Identifier(s) => {
+			let identifier_node =
+				arena.new_node(Node::new(Item::Identifier(s), identifier.span.start_loc));
+			parent.append(identifier_node, arena);
 		}
-		parse_expression(indentation, &for_node, arena, &mut expression_block_1);
-		parse_expression(indentation, &for_node, arena, &mut expression_block_2);
-		parse_block(1, &for_node, arena, tokens)
-	} else {
-		let mut expression: Vec<TokenValue> = Vec::new();
-		let mut found_do = false;
-		while !found_do {
-			let next_token = tokens.remove(0);
-			match next_token.token {
-				Token::Keyword(Keyword::Do) => {
-					found_do = true;
+		_ => panic!(
+			"Expected a name after the 'array' keyword on line {}, column {}.",
+			identifier.span.start_loc.line_num, identifier.span.start_loc.column_num
+		),
+	}
+	let open_bracket = tokens.pop();
+	if open_bracket.token != Token::OpenSquareBracket {
+		panic!(
+			"Expected a '[' after the name of the array on line {}, column {}.",
+			open_bracket.span.start_loc.line_num, open_bracket.span.start_loc.column_num
+		);
+	}
+	let mut dimensions = 0;
+	loop {
+		let (mut size, end) = take_expression(tokens, &[Token::Comma, Token::CloseSquareBracket]);
+		parse_expression(parent, arena, &mut size);
+		dimensions += 1;
+		match end.token {
+			Token::Comma => {}
+			Token::CloseSquareBracket => break,
+			_ => panic!(
+				"Missing a closing square bracket on line {}, column {}",
+				open_bracket.span.start_loc.line_num, open_bracket.span.start_loc.column_num
+			),
+		}
+	}
+	if dimensions > 2 {
+		panic!(
+			"Arrays can have at most two dimensions, but the array on line {} has {}.",
+			open_bracket.span.start_loc.line_num, dimensions
+		);
+	}
+	let end = take_line(tokens).pop_front().unwrap();
+	if end.token != Token::EndOfSequence {
+		panic!(
+			"Expected a new line after the array declaration on line {}, column {}.",
+			end.span.start_loc.line_num, end.span.start_loc.column_num
+		);
+	}
+}
+
+/// A value a switch statement's case can match, used to find duplicate cases.
+#[derive(PartialEq)]
+enum CaseValue {
+	Number(f64),
+	String(String),
+}
+
+/// Finds the value of a case in the tree, if it is a literal.
+fn case_value(arena: &Arena<Node>, node: NodeId) -> Option<CaseValue> {
+	match &arena[node].get().item {
+		Item::String(s) => Some(CaseValue::String(s.clone())),
+		Item::Number(n) if n.radix == 10 => {
+			let mut text = n.base.clone();
+			if let Some(decimal) = &n.decimal {
+				text = format!("{}.{}", text, decimal);
+			}
+			if let Some(exponent) = &n.exponent {
+				text = format!("{}e{}", text, exponent);
+			}
+			text.parse().ok().map(CaseValue::Number)
+		}
+		Item::Number(n) => {
+			u64::from_str_radix(&n.base, n.radix).ok().map(|n| CaseValue::Number(n as f64))
+		}
+		Item::Operator(Operator::Negate) => match case_value(arena, arena[node].first_child()?)? {
+			CaseValue::Number(n) => Some(CaseValue::Number(-n)),
+			CaseValue::String(_) => None,
+		},
+		_ => None,
+	}
+}
+
+fn parse_switch(indentation: i32, parent: &NodeId, arena: &mut Arena<Node>, tokens: &mut Tokens) {
+	let switch_loc = arena[*parent].get().loc;
+	let (mut subject, _) = take_expression(tokens, &[Token::Colon]);
+	let expression_node = arena.new_node(Node::new(Item::Expression, subject[0].span.start_loc));
+	parent.append(expression_node, arena);
+	parse_expression(&expression_node, arena, &mut subject);
+
+	// The values handled so far, and where they were handled.
+	let mut handled: Vec<(CaseValue, Loc)> = Vec::new();
+	// The arms are found at the end of the blocks of the arms before them, so the first arm is
+	// found by skipping to the first token which is not part of the layout.
+	tokens.skip_layout();
+	let mut end = match tokens.peek().token {
+		Token::Keyword(Keyword::Case)
+		| Token::Keyword(Keyword::Default)
+		| Token::Keyword(Keyword::EndSwitch) => tokens.pop(),
+		Token::EndOfSequence => tokens.peek().clone(),
+		_ => panic!(
+			"Expected 'case' or 'default' in the switch statement on line {}, column {}.",
+			tokens.peek().span.start_loc.line_num,
+			tokens.peek().span.start_loc.column_num
+		),
+	};
+	let mut terminators = vec![
+		Token::Keyword(Keyword::Case),
+		Token::Keyword(Keyword::Default),
+		Token::Keyword(Keyword::EndSwitch),
+	];
+	loop {
+		// The arms are indented inside the statement, and their blocks are indented inside them.
+		let arm_indentation = if end.token == Token::Keyword(Keyword::EndSwitch) {
+			indentation
+		} else {
+			indentation + 1
+		};
+		tokens.check_indentation(arm_indentation, &end);
+		let arm = match end.token {
+			// The block stops at another arm without taking it if the arm is not allowed.
+			Token::Keyword(Keyword::Case) | Token::Keyword(Keyword::Default)
+				if !terminators.contains(&end.token) =>
+			{
+				panic!(
+					"'default' must be the last arm of the 'switch' statement on line {}, but '{}' \
+					 was found on line {}, column {}.",
+					switch_loc.line_num,
+					if end.token == Token::Keyword(Keyword::Case) { "case" } else { "default" },
+					end.span.start_loc.line_num,
+					end.span.start_loc.column_num
+				)
+			}
+			Token::Keyword(Keyword::Case) => {
+				let case_node = arena.new_node(Node::new(Item::Case, end.span.start_loc));
+				loop {
+					let (mut value, after) = take_expression(tokens, &[Token::Comma, Token::Colon]);
+					let value_loc = value[0].span.start_loc;
+					parse_expression(&case_node, arena, &mut value);
+					let value_node = arena[case_node].last_child().unwrap();
+					let value = case_value(arena, value_node).unwrap_or_else(|| {
+						panic!(
+							"Expected a number or a string after 'case' on line {}, column {}.",
+							value_loc.line_num, value_loc.column_num
+						)
+					});
+					if let Some((_, loc)) = handled.iter().find(|(handled, _)| *handled == value) {
+						panic!(
+							"Duplicate case on line {}, column {} (the same value is handled on \
+							 line {}).",
+							value_loc.line_num, value_loc.column_num, loc.line_num
+						);
+					}
+					handled.push((value, value_loc));
+					if after.token != Token::Comma {
+						break;
+					}
 				}
-				Token::EndOfSequence | Token::NewLine => panic!(
-					"Expected the 'to' keyword following 'for <variable>=<expression>' on \
-					 line {}, column {}.",
-					next_token.loc.line_num, next_token.loc.column_num
-				),
-				_ => {
-					expression.push(next_token);
+				case_node
+			}
+			Token::Keyword(Keyword::Default) => {
+				if tokens.peek().token == Token::Colon {
+					tokens.pop();
 				}
+				// Nothing but the end of the statement can follow the default arm.
+				terminators = vec![Token::Keyword(Keyword::EndSwitch)];
+				arena.new_node(Node::new(Item::Default, end.span.start_loc))
 			}
-		}
-		parse_expression(indentation, &for_node, arena, &mut expression);
-		parse_block(1, &for_node, arena, tokens)
+			Token::Keyword(Keyword::EndSwitch) => break,
+			_ => panic!(
+				"Missing 'endswitch' for the 'switch' statement on line {}, column {}.",
+				switch_loc.line_num, switch_loc.column_num
+			),
+		};
+		parent.append(arm, arena);
+		let block = arena.new_node(Node::new(Item::Block, tokens.peek().span.start_loc));
+		arm.append(block, arena);
+		end = parse_block(indentation + 2, &block, arena, tokens, &terminators);
 	}
 }
 
+/// Parses the rest of a function (or procedure) declaration after the opening keyword into
+/// `parent`, up to and including the `end` keyword. `kind` is the name of the opening keyword.
 fn parse_function(
 	indentation: i32,
 	parent: &NodeId,
 	arena: &mut Arena<Node>,
-	tokens: &mut Vec<TokenValue>,
+	tokens: &mut Tokens,
+	kind: &str,
+	end: Keyword,
 ) {
-	let function_node =
-		arena.new_node(Node::new(Item::Function, tokens.iter().next().unwrap().loc));
-	parent.append(function_node, arena);
-	let identifier_token = tokens.remove(0);
+	let function_loc = arena.get(*parent).unwrap().get().loc;
+	let identifier_token = tokens.pop();
 	match identifier_token.token {
 		Token::Identifier(s) => {
 			let identifier_node =
-				arena.new_node(Node::new(Item::Identifier(s), identifier_token.loc));
-			function_node.append(identifier_node, arena);
+				arena.new_node(Node::new(Item::Identifier(s), identifier_token.span.start_loc));
+			parent.append(identifier_node, arena);
 		}
 		_ => {
 			panic!(
-				"Expected an identifier after the 'function' keyword on line {}, column {}.",
-				identifier_token.loc.line_num, identifier_token.loc.column_num
+				"Expected an identifier after the '{}' keyword on line {}, column {}.",
+				kind,
+				identifier_token.span.start_loc.line_num,
+				identifier_token.span.start_loc.column_num
 			);
 		}
 	}
-	let open_bracket = tokens.remove(0);
+	let open_bracket = tokens.pop();
 	match open_bracket.token {
 		Token::OpenBracket => {}
 		_ => {
 			panic!(
-				"Expected an opening bracket after the function declaration on line {}, column {}.",
-				open_bracket.loc.line_num, open_bracket.loc.column_num
+				"Expected an opening bracket after the {} declaration on line {}, column {}.",
+				kind, open_bracket.span.start_loc.line_num, open_bracket.span.start_loc.column_num
 			);
 		}
 	}
-	let mut parameter_list: Vec<Item> = Vec::new();
+	let mut parameter_list: Vec<(Item, Passing)> = Vec::new();
+	// Whether the next token should be a parameter (rather than a comma or the closing bracket).
+	let mut expect_parameter = true;
 	loop {
-		let next_token = tokens.remove(0);
+		let next_token = tokens.pop();
+		let loc = next_token.span.start_loc;
 		match next_token.token {
-			Token::CloseBracket => break,
-			Token::Identifier(s) => parameter_list.push(Item::Identifier(s)),
-			Token::Comma => {}
-			_ => {
-				panic!(
-					"Unexpected token on line {}, column {}.",
-					next_token.loc.line_num, next_token.loc.column_num
-				);
+			Token::CloseBracket if !expect_parameter || parameter_list.is_empty() => break,
+			Token::Identifier(s) if expect_parameter => {
+				expect_parameter = false;
+				let mut passing = Passing::ByVal;
+				if tokens.peek().token == Token::Colon {
+					tokens.pop();
+					let mode = tokens.pop();
+					let loc = mode.span.start_loc;
+					passing = match mode.token {
+						Token::Keyword(Keyword::ByVal) => Passing::ByVal,
+						Token::Keyword(Keyword::ByRef) => Passing::ByRef,
+						_ => panic!(
+							"Expected 'byVal' or 'byRef' after the parameter on line {}, \
+							 column {}.",
+							loc.line_num, loc.column_num
+						),
+					};
+				}
+				parameter_list.push((Item::Identifier(s), passing));
+			}
+			Token::Comma if !expect_parameter => expect_parameter = true,
+			_ if expect_parameter => {
+				panic!("Expected a parameter on line {}, column {}.", loc.line_num, loc.column_num)
 			}
+			_ => panic!(
+				"Expected a comma or a closing bracket after the parameter on line {}, column {}.",
+				loc.line_num, loc.column_num
+			),
 		}
 	}
 	let parameter_list_node =
-		arena.new_node(Node::new(Item::ParameterList(parameter_list), open_bracket.loc));
-	let new_line = tokens.remove(0);
+		arena.new_node(Node::new(Item::ParameterList(parameter_list), open_bracket.span.start_loc));
+	parent.append(parameter_list_node, arena);
+	let new_line = tokens.pop();
 	match new_line.token {
 		Token::NewLine => {}
 		_ => {
 			panic!(
 				"Expected a new line on line {}, column {}.",
-				new_line.loc.line_num, new_line.loc.column_num
+				new_line.span.start_loc.line_num, new_line.span.start_loc.column_num
 			);
 		}
 	}
-	let block_node = arena.new_node(Node::new(Item::Block, Loc::new(new_line.loc.line_num + 1, 0)));
-	parse_block(1, &block_node, arena, tokens);
+	let block_node = arena.new_node(Node::new(Item::Block, tokens.peek().span.start_loc));
+	parent.append(block_node, arena);
+	let end_token =
+		parse_block(indentation + 1, &block_node, arena, tokens, &[Token::Keyword(end.clone())]);
+	tokens.check_indentation(indentation, &end_token);
+	if end_token.token != Token::Keyword(end) {
+		panic!(
+			"Missing 'end{}' for the {} on line {}, column {}.",
+			kind, kind, function_loc.line_num, function_loc.column_num
+		);
+	}
+}
+
+/// Removes the tokens up to one of the `terminators` (which is also removed), or up to the end of
+/// the line (which is left in place), returning them with an `EndOfSequence` token in place of the
+/// terminator, along with the token which ended them.
+fn take_expression(
+	tokens: &mut Tokens,
+	terminators: &[Token],
+) -> (VecDeque<TokenValue>, TokenValue) {
+	let mut expression = VecDeque::new();
+	loop {
+		let end = match &tokens.peek().token {
+			token if terminators.contains(token) => tokens.pop(),
+			Token::NewLine | Token::EndOfSequence => tokens.peek().clone(),
+			_ => {
+				expression.push_back(tokens.pop());
+				continue;
+			}
+		};
+		expression.push_back(TokenValue::new(Token::EndOfSequence, end.span));
+		return (expression, end);
+	}
+}
+
+/// Removes the tokens up to the end of the line (including the new line, but leaving the end of the
+/// program in place), returning them with an `EndOfSequence` token in place of the line's end.
+fn take_line(tokens: &mut Tokens) -> VecDeque<TokenValue> {
+	let mut line = VecDeque::new();
+	loop {
+		match tokens.peek().token {
+			Token::NewLine => {
+				let end = tokens.pop();
+				line.push_back(TokenValue::new(Token::EndOfSequence, end.span));
+				return line;
+			}
+			Token::EndOfSequence => {
+				line.push_back(tokens.peek().clone());
+				return line;
+			}
+			_ => line.push_back(tokens.pop()),
+		}
+	}
+}
+
+/// Whether `token` can only appear at the end of a block.
+fn ends_block(token: &Token) -> bool {
+	matches!(
+		token,
+		Token::Keyword(
+			Keyword::ElseIf
+				| Keyword::Else
+				| Keyword::EndIf
+				| Keyword::EndWhile
+				| Keyword::EndFor
+				| Keyword::Next
+				| Keyword::EndFunction
+				| Keyword::EndProcedure
+				| Keyword::Case
+				| Keyword::Default
+				| Keyword::EndSwitch
+				| Keyword::Until
+		)
+	)
 }
 
+/// Parses statements into `parent` until one of the `terminators` is found, which is removed and
+/// returned. If the end of the program (or the end of some other block) is found first, it is
+/// returned but left in place. A warning is recorded for each statement which is not indented by
+/// `indentation` levels, but the caller checks the indentation of the token which ends the block.
 fn parse_block(
 	indentation: i32,
 	parent: &NodeId,
 	arena: &mut Arena<Node>,
-	tokens: &mut Vec<TokenValue>,
-) {
-	let mut finished = false;
-	while !finished {
-		for i in 1..indentation {
-			let next = tokens.remove(0);
-			match next.token {
-				Token::Tab => {}
-				_ => finished = true,
+	tokens: &mut Tokens,
+	terminators: &[Token],
+) -> TokenValue {
+	loop {
+		tokens.skip_layout();
+		match &tokens.peek().token {
+			Token::EndOfSequence => return tokens.peek().clone(),
+			token if terminators.contains(token) => return tokens.pop(),
+			token if ends_block(token) => return tokens.peek().clone(),
+			_ => {
+				let start = tokens.peek().clone();
+				tokens.check_indentation(indentation, &start);
+				parse_statement(indentation, parent, arena, tokens);
 			}
 		}
-		parse_statement(indentation, parent, arena, tokens)
 	}
 }
 
@@ -454,66 +1116,149 @@ fn parse_statement(
 	indentation: i32,
 	parent: &NodeId,
 	arena: &mut Arena<Node>,
-	tokens: &mut Vec<TokenValue>,
+	tokens: &mut Tokens,
 ) {
-	let lexitem = tokens.remove(0);
+	let lexitem = tokens.pop();
 	match lexitem.token {
 		Token::Keyword(Keyword::If) => {
-			let new_node = arena.new_node(Node::new(Item::If, lexitem.loc));
+			let new_node = arena.new_node(Node::new(Item::If, lexitem.span.start_loc));
 			parent.append(new_node, arena);
 			parse_if(indentation, &new_node, arena, tokens);
 		}
 		Token::Keyword(Keyword::While) => {
-			let new_node = arena.new_node(Node::new(Item::While, lexitem.loc));
+			let new_node = arena.new_node(Node::new(Item::While, lexitem.span.start_loc));
 			parent.append(new_node, arena);
 			parse_while(indentation, &new_node, arena, tokens);
 		}
+		Token::Keyword(Keyword::Do) => {
+			let new_node = arena.new_node(Node::new(Item::DoUntil, lexitem.span.start_loc));
+			parent.append(new_node, arena);
+			parse_do_until(indentation, &new_node, arena, tokens);
+		}
 		Token::Keyword(Keyword::For) => {
-			let new_node = arena.new_node(Node::new(Item::For, lexitem.loc));
+			let new_node = arena.new_node(Node::new(Item::For, lexitem.span.start_loc));
 			parent.append(new_node, arena);
-			parse_for(indentation, &new_node, arena, tokens)
+			parse_for(indentation, &new_node, arena, tokens);
+		}
+		Token::Keyword(Keyword::Switch) => {
+			let new_node = arena.new_node(Node::new(Item::Switch, lexitem.span.start_loc));
+			parent.append(new_node, arena);
+			parse_switch(indentation, &new_node, arena, tokens);
 		}
 		Token::Keyword(Keyword::Function) => {
-			let new_node = arena.new_node(Node::new(Item::Function, lexitem.loc));
+			let new_node = arena.new_node(Node::new(Item::Function, lexitem.span.start_loc));
+			parent.append(new_node, arena);
+			parse_function(indentation, &new_node, arena, tokens, "function", Keyword::EndFunction);
+		}
+		Token::Keyword(Keyword::Procedure) => {
+			let new_node = arena.new_node(Node::new(Item::Procedure, lexitem.span.start_loc));
+			parent.append(new_node, arena);
+			parse_function(
+				indentation,
+				&new_node,
+				arena,
+				tokens,
+				"procedure",
+				Keyword::EndProcedure,
+			);
+		}
+		Token::Keyword(Keyword::Return) => {
+			let return_node = arena.new_node(Node::new(Item::Return, lexitem.span.start_loc));
+			parent.append(return_node, arena);
+			let mut expression = take_line(tokens);
+			// The expression is only ended by the end of the line if nothing is returned.
+			if expression.len() > 1 {
+				let expression_node =
+					arena.new_node(Node::new(Item::Expression, expression[0].span.start_loc));
+				return_node.append(expression_node, arena);
+				parse_expression(&expression_node, arena, &mut expression);
+			}
+		}
+		Token::Keyword(Keyword::Array) => {
+			let new_node = arena.new_node(Node::new(Item::Array, lexitem.span.start_loc));
 			parent.append(new_node, arena);
-			parse_function(indentation, &new_node, arena, tokens);
+			parse_array(&new_node, arena, tokens);
+		}
+		Token::Identifier(_) if tokens.peek().token == Token::OpenSquareBracket => {
+			// The target is everything up to the first '=' which is not inside brackets.
+			let mut target = VecDeque::from(vec![lexitem.clone()]);
+			let mut depth = 0;
+			loop {
+				match tokens.peek().token {
+					Token::OpenBracket | Token::OpenSquareBracket => depth += 1,
+					Token::CloseBracket | Token::CloseSquareBracket => depth -= 1,
+					Token::Operator(Operator::Equals) if depth == 0 => break,
+					Token::NewLine | Token::EndOfSequence => panic!(
+						"Expected an assignment to the array element on line {}, column {}.",
+						lexitem.span.start_loc.line_num, lexitem.span.start_loc.column_num
+					),
+					_ => {}
+				}
+				target.push_back(tokens.pop());
+			}
+			let equals = tokens.pop();
+			target.push_back(TokenValue::new(Token::EndOfSequence, equals.span));
+
+			let assign_node = arena.new_node(Node::new(Item::Assign, lexitem.span.start_loc));
+			parent.append(assign_node, arena);
+			parse_expression(&assign_node, arena, &mut target);
+			let target_node = arena[assign_node].first_child().unwrap();
+			if arena[target_node].get().item != Item::Index {
+				panic!(
+					"Expected an assignment to the array element on line {}, column {}.",
+					lexitem.span.start_loc.line_num, lexitem.span.start_loc.column_num
+				);
+			}
+			let assign_expression =
+				arena.new_node(Node::new(Item::Expression, equals.span.start_loc));
+			assign_node.append(assign_expression, arena);
+			let mut expression = take_line(tokens);
+			parse_expression(&assign_expression, arena, &mut expression);
+		}
+		Token::Identifier(_) if tokens.peek().token == Token::OpenBracket => {
+			let mut expression = VecDeque::from(vec![lexitem.clone()]);
+			expression.append(&mut take_line(tokens));
+			parse_expression(parent, arena, &mut expression);
+			let statement = arena[*parent].last_child().unwrap();
+			match arena[statement].get().item {
+				Item::Call(_) => {}
+				_ => panic!(
+					"Expected an assignment or a function call on line {}, column {}.",
+					lexitem.span.start_loc.line_num, lexitem.span.start_loc.column_num
+				),
+			}
 		}
 		Token::Identifier(s) => {
-			let next = tokens.remove(0);
+			// A name on its own (such as a misspelled keyword) is not a statement.
+			if let Token::NewLine | Token::EndOfSequence = tokens.peek().token {
+				panic!(
+					"Invalid statement on line {}, column {}. Expected an assignment or a function \
+					 call.",
+					lexitem.span.start_loc.line_num, lexitem.span.start_loc.column_num
+				);
+			}
+			let next = tokens.pop();
 			match next.token {
 				Token::Operator(Operator::Equals) => {
-					let assign_node = arena.new_node(Node::new(Item::Assign, lexitem.loc));
+					let assign_node =
+						arena.new_node(Node::new(Item::Assign, lexitem.span.start_loc));
 					parent.append(assign_node, arena);
 
-					let var_name = arena.new_node(Node::new(Item::Identifier(s), lexitem.loc));
-					let assign_expression = arena.new_node(Node::new(Item::Expression, next.loc));
+					let var_name =
+						arena.new_node(Node::new(Item::Identifier(s), lexitem.span.start_loc));
+					let assign_expression =
+						arena.new_node(Node::new(Item::Expression, next.span.start_loc));
 
 					assign_node.append(var_name, arena);
 					assign_node.append(assign_expression, arena);
 
-					let mut expression: Vec<TokenValue> = Vec::new();
-					loop {
-						let next_token = tokens.remove(0);
-						match next_token.token {
-							Token::NewLine | Token::EndOfSequence => {
-								expression.push(TokenValue::new(
-									Token::EndOfSequence,
-									next_token.loc.line_num,
-									next_token.loc.column_num,
-								));
-								break;
-							}
-							_ => expression.push(next_token),
-						}
-					}
-
-					parse_expression(indentation, &assign_expression, arena, &mut expression)
+					let mut expression = take_line(tokens);
+					parse_expression(&assign_expression, arena, &mut expression)
 				}
-				Token::NewLine => {}
 				_ => {
 					panic!(
 						"Invalid token on line {}, column {}.",
-						next.loc.line_num, next.loc.column_num
+						next.span.start_loc.line_num, next.span.start_loc.column_num
 					);
 				}
 			}
@@ -521,14 +1266,36 @@ fn parse_statement(
 		Token::EndOfSequence => {}
 		_ => panic!(
 			"Unexpected token on line {}, column {}.",
-			lexitem.loc.line_num, lexitem.loc.column_num
+			lexitem.span.start_loc.line_num, lexitem.span.start_loc.column_num
 		),
 	}
 }
 
 pub fn parser(tokens: &mut Vec<TokenValue>) -> (Arena<Node>, NodeId) {
+	let (arena, program_node, _) = parser_with_warnings(tokens);
+	(arena, program_node)
+}
+
+/// Parses the program in the same way as `parser`, also returning a warning for each problem which
+/// does not stop it from being compiled.
+pub fn parser_with_warnings(tokens: &mut Vec<TokenValue>) -> (Arena<Node>, NodeId, Vec<Warning>) {
+	// Comments are kept in the token stream for tooling, but have no meaning to the parser.
+	let tokens = &mut Tokens {
+		tokens: tokens
+			.drain(..)
+			.filter(|token| !matches!(token.token, Token::Comment(_)))
+			.collect(),
+		depth: 0,
+		warnings: Vec::new(),
+	};
 	let arena = &mut Arena::new();
 	let program_node = arena.new_node(Node::new(Item::Program, Loc::new(0, 0)));
-	parse_statement(0, &program_node, arena, tokens);
-	(arena.clone(), program_node)
+	let end = parse_block(0, &program_node, arena, tokens, &[]);
+	if end.token != Token::EndOfSequence {
+		panic!(
+			"Unexpected token on line {}, column {}.",
+			end.span.start_loc.line_num, end.span.start_loc.column_num
+		);
+	}
+	(arena.clone(), program_node, tokens.warnings.clone())
 }
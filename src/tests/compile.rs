@@ -1 +1,103 @@
 use crate::compile;
+
+#[test]
+fn assignment_and_calls() {
+	let output = compile(String::from("x = 2 * -y ^ 2\nprint(x DIV 3, \"done\")")).unwrap();
+	assert!(output == "var x = (2 * (-(y ** 2)));\nconsole.log(Math.trunc(x / 3), \"done\");\n");
+}
+
+#[test]
+fn booleans() {
+	let output = compile(String::from("found = false\nprint(true, not found)")).unwrap();
+	assert!(output == "var found = false;\nconsole.log(true, (!found));\n");
+}
+
+#[test]
+fn membership() {
+	let output = compile(String::from("found = \"a\" in letters and 2 in [1, 2]")).unwrap();
+	assert!(output == "var found = ((letters.includes(\"a\")) && ([1, 2].includes(2)));\n");
+}
+
+#[test]
+fn leading_zeros() {
+	let output = compile(String::from("x = 010\ny = 00.5\nz = 0")).unwrap();
+	assert!(output == "var x = 10;\nvar y = 0.5;\nvar z = 0;\n");
+}
+
+#[test]
+fn procedures_with_parameters_passed_by_reference() {
+	let input = "procedure clear(list:byRef, size:byVal)\n\tlist[0] = size\nendprocedure";
+	let output = compile(String::from(input)).unwrap();
+	assert!(output == "function clear(list, size) {\nlist[0] = size;\n}\n");
+}
+
+#[test]
+fn for_in_loops() {
+	let output =
+		compile(String::from("for letter in \"abc\"\n\tprint(letter)\nnext letter")).unwrap();
+	assert!(output == "for (var letter of \"abc\") {\nconsole.log(letter);\n}\n");
+}
+
+#[test]
+fn count_controlled_loops() {
+	let output = compile(String::from("for i = 10 to 0 step -2\n\tprint(i)\nnext i")).unwrap();
+	let expected = [
+		"for (var i = 10, $end_i = 0, $step_i = (-2); $step_i >= 0 ? i <= $end_i : i >= $end_i; i \
+		 += $step_i) {",
+		"console.log(i);",
+		"}",
+		"",
+	];
+	assert!(output == expected.join("\n"));
+}
+
+#[test]
+fn switch_statements() {
+	let input = "switch x:\n\tcase 1, -1:\n\t\tprint(\"one\")\n\tdefault:\n\t\tprint(x)\nendswitch";
+	let output = compile(String::from(input)).unwrap();
+	let expected = [
+		"switch (x) {",
+		"case 1:",
+		"case (-1):",
+		"console.log(\"one\");",
+		"break;",
+		"default:",
+		"console.log(x);",
+		"break;",
+		"}",
+		"",
+	];
+	assert!(output == expected.join("\n"));
+}
+
+#[test]
+fn do_until_loops() {
+	let output = compile(String::from("do\n\tx = input(\"x?\")\nuntil x == \"y\"")).unwrap();
+	assert!(output == "do {\nvar x = prompt(\"x?\");\n} while (!(x === \"y\"));\n");
+}
+
+#[test]
+fn arrays() {
+	let input =
+		"array board[3, 3]\nboard[1, 2] = [1, 2][0]\nfor row in board\n\tprint(row)\nnext row";
+	let output = compile(String::from(input)).unwrap();
+	let expected = [
+		"var board = Array.from({ length: 3 }, () => new Array(3));",
+		"board[1][2] = [1, 2][0];",
+		"for (var row of board) {",
+		"console.log(row);",
+		"}",
+		"",
+	];
+	assert!(output == expected.join("\n"));
+}
+
+#[test]
+fn indentation_warnings() {
+	let output = compile(String::from("while x\nx = 0\nendwhile")).unwrap();
+	assert!(
+		output
+			== "console.warn(\"Misleading indentation on line 2, column 1. Expected 1 level of \
+			    indentation, but found 0.\");\nwhile (x) {\nvar x = 0;\n}\n"
+	);
+}
@@ -0,0 +1,85 @@
+use crate::{
+	highlight,
+	parser::{
+		highlight::{classify, SemanticKind},
+		lexer::*,
+	},
+};
+
+/// Classifies `input`, returning the text and kind of each classified token.
+fn kinds(input: &str, config: LexerConfig) -> Vec<(&str, SemanticKind)> {
+	let tokens = lexer_with_config(input, config).unwrap();
+	classify(&tokens)
+		.into_iter()
+		.map(|token| (&input[token.span.start..token.span.end], token.kind))
+		.collect()
+}
+
+#[test]
+fn simple_statements() {
+	use SemanticKind::*;
+	let output = kinds("x = 1.5 + y // add\nprint(\"hi\")", LexerConfig::default());
+	assert!(
+		output
+			== vec![
+				("x", Identifier),
+				("=", Operator),
+				("1.5", Number),
+				("+", Operator),
+				("y", Identifier),
+				("// add", Comment),
+				("print", FunctionName),
+				("\"hi\"", String),
+			]
+	);
+}
+
+#[test]
+fn functions_and_parameters() {
+	use SemanticKind::*;
+	let input = "function add(a, b)\n\treturn a + b\nendfunction\nc = add(a, 1)";
+	let output = kinds(input, LexerConfig::default());
+	assert!(
+		output
+			== vec![
+				("function", Keyword),
+				("add", FunctionName),
+				("a", Parameter),
+				("b", Parameter),
+				("return", Keyword),
+				("a", Parameter),
+				("+", Operator),
+				("b", Parameter),
+				("endfunction", Keyword),
+				("c", Identifier),
+				("=", Operator),
+				("add", FunctionName),
+				// Outside of the function, 'a' is no longer a parameter.
+				("a", Identifier),
+				("1", Number),
+			]
+	);
+}
+
+#[test]
+fn lossless_comments() {
+	let config = LexerConfig { lossless: true, ..LexerConfig::default() };
+	let output = kinds("// start\nx = true // end", config);
+	assert!(
+		output
+			== vec![
+				("// start", SemanticKind::Comment),
+				("x", SemanticKind::Identifier),
+				("=", SemanticKind::Operator),
+				("true", SemanticKind::Keyword),
+				("// end", SemanticKind::Comment),
+			]
+	);
+}
+
+#[test]
+fn highlight_ranges() {
+	// "é" is two bytes but one UTF-16 code unit, and "😀" is four bytes but two code units.
+	let output = highlight(String::from("é = \"😀\" ? 1"));
+	assert!(output == vec![0, 1, 1, 2, 3, 4, 4, 8, 3, 11, 12, 2]);
+}
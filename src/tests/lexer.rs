@@ -1,9 +1,13 @@
-use crate::parser::lexer::*;
+use crate::parser::{
+	incremental::{relex, TextEdit},
+	keywords::Dialect,
+	lexer::*,
+};
 use std::any::Any;
 
 #[test]
 fn number() {
-	let output = lexer(&String::from("1.03e8"));
+	let output = lexer(&String::from("1.03e8")).unwrap();
 	let first_token = &output.get(0).unwrap().token;
 	assert!(
 		first_token
@@ -11,13 +15,79 @@ fn number() {
 				base: String::from("1"),
 				decimal: Some(String::from("03")),
 				exponent: Some(String::from("8")),
+				radix: 10,
 			}))
 	)
 }
 
+#[test]
+fn number_with_nine() {
+	let output = lexer("90.9e9").unwrap();
+	assert!(
+		output.get(0).unwrap().token
+			== Token::Literal(LiteralValue::Number(Number {
+				base: String::from("90"),
+				decimal: Some(String::from("9")),
+				exponent: Some(String::from("9")),
+				radix: 10,
+			}))
+	);
+}
+
+#[test]
+fn signed_exponents() {
+	let output = lexer("1e-3 2E+4").unwrap();
+	match &output.get(0).unwrap().token {
+		Token::Literal(LiteralValue::Number(number)) => {
+			assert!(number.exponent == Some(String::from("-3")))
+		}
+		token => panic!("Unexpected token {:?}", token),
+	}
+	match &output.get(1).unwrap().token {
+		Token::Literal(LiteralValue::Number(number)) => {
+			assert!(number.exponent == Some(String::from("4")))
+		}
+		token => panic!("Unexpected token {:?}", token),
+	}
+	assert!(lexer("1e-").is_err());
+}
+
+#[test]
+fn prefixed_numbers() {
+	let numbers = vec![("0x1F", "1F", 16), ("0b1010", "1010", 2), ("0o17", "17", 8)];
+	for (source, base, radix) in numbers {
+		let output = lexer(source).unwrap();
+		assert!(
+			output.get(0).unwrap().token
+				== Token::Literal(LiteralValue::Number(Number {
+					base: String::from(base),
+					decimal: None,
+					exponent: None,
+					radix,
+				}))
+		);
+		assert!(output.get(1).unwrap().token == Token::EndOfSequence);
+	}
+}
+
+#[test]
+fn out_of_range_digits() {
+	match lexer("x = 0b1021").unwrap_err().as_slice() {
+		[LexError::MalformedNumber { reason, loc }] => {
+			assert!(reason == "'2' is not a valid binary digit");
+			assert!(*loc == Loc::new(1, 9));
+		}
+		errors => panic!("Unexpected errors {:?}", errors),
+	}
+	match lexer("0x").unwrap_err().as_slice() {
+		[LexError::MalformedNumber { reason, .. }] => assert!(reason == "no digits after the '0x'"),
+		errors => panic!("Unexpected errors {:?}", errors),
+	}
+}
+
 #[test]
 fn assignment() {
-	let mut output = lexer(&String::from("x=1"));
+	let mut output = lexer(&String::from("x=1")).unwrap();
 	let identifier = output.get(0).unwrap();
 	let equals = output.get(1).unwrap();
 	let value = output.get(2).unwrap();
@@ -30,6 +100,7 @@ fn assignment() {
 				base: String::from("1"),
 				decimal: None,
 				exponent: None,
+				radix: 10,
 			}))
 	);
 	assert!(eos.token == Token::EndOfSequence);
@@ -37,7 +108,7 @@ fn assignment() {
 
 #[test]
 fn double_equals() {
-	let mut output = lexer(&String::from("x==1"));
+	let mut output = lexer(&String::from("x==1")).unwrap();
 	let identifier = output.get(0).unwrap();
 	let equals = output.get(1).unwrap();
 	let value = output.get(2).unwrap();
@@ -50,6 +121,7 @@ fn double_equals() {
 				base: String::from("1"),
 				decimal: None,
 				exponent: None,
+				radix: 10,
 			}))
 	);
 	assert!(eos.token == Token::EndOfSequence);
@@ -57,7 +129,7 @@ fn double_equals() {
 
 #[test]
 fn divide() {
-	let mut output = lexer(&String::from("x/y"));
+	let mut output = lexer(&String::from("x/y")).unwrap();
 	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
 	assert!(output.get(1).unwrap().token == Token::Operator(Operator::Divide));
 	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("y")));
@@ -65,15 +137,40 @@ fn divide() {
 
 #[test]
 fn integer_divide() {
-	let mut output = lexer(&String::from("x//y"));
+	let mut output = lexer(&String::from("x DIV y")).unwrap();
 	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
 	assert!(output.get(1).unwrap().token == Token::Operator(Operator::IntegerDivide));
 	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("y")));
 }
 
+#[test]
+fn modulo() {
+	let output = lexer(&String::from("x MOD y")).unwrap();
+	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
+	assert!(output.get(1).unwrap().token == Token::Operator(Operator::Modulo));
+	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("y")));
+}
+
+#[test]
+fn exponent() {
+	let output = lexer(&String::from("x^2")).unwrap();
+	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
+	assert!(output.get(1).unwrap().token == Token::Operator(Operator::Exponent));
+}
+
+#[test]
+fn comment() {
+	let output = lexer(&String::from("x = 1 // set x\n// done")).unwrap();
+	assert!(output.get(3).unwrap().token == Token::Comment(String::from(" set x")));
+	assert!(output.get(3).unwrap().span.start == 6);
+	assert!(output.get(4).unwrap().token == Token::NewLine);
+	assert!(output.get(5).unwrap().token == Token::Comment(String::from(" done")));
+	assert!(output.get(6).unwrap().token == Token::EndOfSequence);
+}
+
 #[test]
 fn add() {
-	let mut output = lexer(&String::from("x+y"));
+	let mut output = lexer(&String::from("x+y")).unwrap();
 	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
 	assert!(output.get(1).unwrap().token == Token::Operator(Operator::Plus));
 	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("y")));
@@ -81,7 +178,7 @@ fn add() {
 
 #[test]
 fn multiply() {
-	let mut output = lexer(&String::from("x*y"));
+	let mut output = lexer(&String::from("x*y")).unwrap();
 	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
 	assert!(output.get(1).unwrap().token == Token::Operator(Operator::Times));
 	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("y")));
@@ -89,7 +186,7 @@ fn multiply() {
 
 #[test]
 fn minus() {
-	let mut output = lexer(&String::from("x-y"));
+	let mut output = lexer(&String::from("x-y")).unwrap();
 	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
 	assert!(output.get(1).unwrap().token == Token::Operator(Operator::Minus));
 	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("y")));
@@ -97,7 +194,7 @@ fn minus() {
 
 #[test]
 fn and() {
-	let mut output = lexer(&String::from("x and y"));
+	let mut output = lexer(&String::from("x and y")).unwrap();
 	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
 	assert!(output.get(1).unwrap().token == Token::Operator(Operator::And));
 	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("y")));
@@ -105,14 +202,199 @@ fn and() {
 
 #[test]
 fn or() {
-	let mut output = lexer(&String::from("x or y"));
+	let mut output = lexer(&String::from("x or y")).unwrap();
 	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
 	assert!(output.get(1).unwrap().token == Token::Operator(Operator::Or));
 	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("y")));
 }
 
 #[test]
-fn string() {}
+fn comparisons() {
+	let comparisons = vec![
+		("!=", Operator::NotEquals),
+		("<>", Operator::NotEquals),
+		("≠", Operator::NotEquals),
+		("<", Operator::LessThan),
+		("<=", Operator::LessThanOrEqualTo),
+		("≤", Operator::LessThanOrEqualTo),
+		(">", Operator::GreaterThan),
+		(">=", Operator::GreaterThanOrEqualTo),
+		("≥", Operator::GreaterThanOrEqualTo),
+	];
+	for (source, operator) in comparisons {
+		let output = lexer(&format!("x {} y", source)).unwrap();
+		assert!(output.len() == 4);
+		assert!(output[1].token == Token::Operator(operator));
+		assert!(output[1].span.end - output[1].span.start == source.len());
+	}
+}
+
+#[test]
+fn brackets_and_colons() {
+	let output = lexer("names[2]\ncase \"A\":").unwrap();
+	assert!(output[1].token == Token::OpenSquareBracket);
+	assert!(output[3].token == Token::CloseSquareBracket);
+	assert!(output[7].token == Token::Colon);
+}
+
+#[test]
+fn identifiers() {
+	for identifier in ["player_score", "total9", "_hidden", "größe", "名前"] {
+		let output = lexer(identifier).unwrap();
+		assert!(output.get(0).unwrap().token == Token::Identifier(String::from(identifier)));
+		assert!(output.get(1).unwrap().token == Token::EndOfSequence);
+	}
+}
+
+#[test]
+fn identifier_stops_at_other_characters() {
+	let output = lexer("print(name,age)").unwrap();
+	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("print")));
+	assert!(output.get(1).unwrap().token == Token::OpenBracket);
+	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("name")));
+	assert!(output.get(3).unwrap().token == Token::Comma);
+	assert!(output.get(4).unwrap().token == Token::Identifier(String::from("age")));
+	assert!(output.get(5).unwrap().token == Token::CloseBracket);
+}
+
+#[test]
+fn keywords() {
+	let keywords = vec![
+		("if", Keyword::If),
+		("then", Keyword::Then),
+		("elseif", Keyword::ElseIf),
+		("else", Keyword::Else),
+		("endif", Keyword::EndIf),
+		("for", Keyword::For),
+		("to", Keyword::To),
+		("step", Keyword::Step),
+		("next", Keyword::Next),
+		("endfor", Keyword::EndFor),
+		("while", Keyword::While),
+		("endwhile", Keyword::EndWhile),
+		("do", Keyword::Do),
+		("until", Keyword::Until),
+		("switch", Keyword::Switch),
+		("case", Keyword::Case),
+		("default", Keyword::Default),
+		("endswitch", Keyword::EndSwitch),
+		("function", Keyword::Function),
+		("endfunction", Keyword::EndFunction),
+		("procedure", Keyword::Procedure),
+		("endprocedure", Keyword::EndProcedure),
+		("return", Keyword::Return),
+		("global", Keyword::Global),
+		("array", Keyword::Array),
+		("class", Keyword::Class),
+		("endclass", Keyword::EndClass),
+		("inherits", Keyword::Inherits),
+		("new", Keyword::New),
+		("super", Keyword::Super),
+		("public", Keyword::Public),
+		("private", Keyword::Private),
+		("byVal", Keyword::ByVal),
+		("byRef", Keyword::ByRef),
+	];
+	for (source, keyword) in keywords {
+		let output = lexer(source).unwrap();
+		assert!(output.get(0).unwrap().token == Token::Keyword(keyword));
+	}
+}
+
+#[test]
+fn keywords_ignore_case() {
+	let output = lexer("IF x Then\nendIF").unwrap();
+	assert!(output.get(0).unwrap().token == Token::Keyword(Keyword::If));
+	assert!(output.get(2).unwrap().token == Token::Keyword(Keyword::Then));
+	assert!(output.get(4).unwrap().token == Token::Keyword(Keyword::EndIf));
+	// Identifiers keep the case they were written in.
+	assert!(output.get(1).unwrap().token == Token::Identifier(String::from("x")));
+}
+
+#[test]
+fn case_sensitive_keywords() {
+	let config = LexerConfig { case_sensitive: true, ..LexerConfig::default() };
+	let output = lexer_with_config("IF if div DIV", config).unwrap();
+	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("IF")));
+	assert!(output.get(1).unwrap().token == Token::Keyword(Keyword::If));
+	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("div")));
+	assert!(output.get(3).unwrap().token == Token::Operator(Operator::IntegerDivide));
+}
+
+#[test]
+fn dialects() {
+	let config =
+		LexerConfig { case_sensitive: true, dialect: Dialect::Aqa, ..LexerConfig::default() };
+	let output = lexer_with_config("REPEAT ENDIF endif", config).unwrap();
+	assert!(output.get(0).unwrap().token == Token::Keyword(Keyword::Do));
+	assert!(output.get(1).unwrap().token == Token::Keyword(Keyword::EndIf));
+	assert!(output.get(2).unwrap().token == Token::Identifier(String::from("endif")));
+
+	let config = LexerConfig { dialect: Dialect::Cambridge, ..LexerConfig::default() };
+	let output = lexer_with_config("OTHERWISE EndCase NEXT", config).unwrap();
+	assert!(output.get(0).unwrap().token == Token::Keyword(Keyword::Default));
+	assert!(output.get(1).unwrap().token == Token::Keyword(Keyword::EndSwitch));
+	assert!(output.get(2).unwrap().token == Token::Keyword(Keyword::Next));
+
+	// Aliases belong to their own dialect only.
+	let output = lexer("REPEAT").unwrap();
+	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("REPEAT")));
+}
+
+#[test]
+fn in_operator() {
+	let output = lexer("for letter in word").unwrap();
+	assert!(output.get(2).unwrap().token == Token::Operator(Operator::In));
+}
+
+#[test]
+fn string() {
+	let output = lexer(&String::from("x = \"hello world\"")).unwrap();
+	assert!(output.get(0).unwrap().token == Token::Identifier(String::from("x")));
+	assert!(output.get(1).unwrap().token == Token::Operator(Operator::Equals));
+	assert!(
+		output.get(2).unwrap().token
+			== Token::Literal(LiteralValue::String(String::from("hello world")))
+	);
+	assert!(output.get(3).unwrap().token == Token::EndOfSequence);
+}
+
+#[test]
+fn string_escapes() {
+	let output = lexer(&String::from(r#""say \"hi\"\n\tand \\ go""#)).unwrap();
+	assert!(
+		output.get(0).unwrap().token
+			== Token::Literal(LiteralValue::String(String::from("say \"hi\"\n\tand \\ go")))
+	);
+}
+
+#[test]
+fn unterminated_string() {
+	let errors = lexer(&String::from("\"oops\nz=2")).unwrap_err();
+	assert!(errors == vec![LexError::UnterminatedString { loc: Loc::new(1, 1) }]);
+	assert!(errors[0].message() == "Unterminated string starting on line 1, column 1.");
+}
+
+#[test]
+fn reports_every_error() {
+	let errors = lexer(&String::from("x = 1 ? 2\ny = \"a\\qb\"")).unwrap_err();
+	assert!(errors.len() == 2);
+	match (&errors[0], &errors[1]) {
+		(
+			LexError::UnexpectedChar { found: '?', .. },
+			LexError::UnknownEscape { found: 'q', .. },
+		) => {}
+		_ => panic!("Unexpected errors {:?}", errors),
+	}
+}
+
+#[test]
+fn lone_exclamation_mark() {
+	match lexer(&String::from("x ! y")).unwrap_err().as_slice() {
+		[LexError::ExpectedEquals { .. }] => {}
+		errors => panic!("Unexpected errors {:?}", errors),
+	}
+}
 
 #[test]
 fn list() {}
@@ -128,3 +410,245 @@ fn while_loop() {}
 
 #[test]
 fn function() {}
+
+#[test]
+fn spans() {
+	let output = lexer(&String::from("x >= 10\n\"é\" + y")).unwrap();
+	let spans: Vec<Span> = output.iter().map(|token| token.span).collect();
+	assert!(spans[0] == Span::new(0, 1, Loc::new(1, 1), Loc::new(1, 2)));
+	assert!(output[1].token == Token::Operator(Operator::GreaterThanOrEqualTo));
+	assert!(spans[1] == Span::new(2, 4, Loc::new(1, 3), Loc::new(1, 5)));
+	assert!(spans[2] == Span::new(5, 7, Loc::new(1, 6), Loc::new(1, 8)));
+	assert!(output[3].token == Token::NewLine);
+	assert!(spans[3] == Span::new(7, 8, Loc::new(1, 8), Loc::new(2, 1)));
+	// The string contains a two byte character, so byte offsets and columns differ.
+	assert!(spans[4] == Span::new(8, 12, Loc::new(2, 1), Loc::new(2, 4)));
+	assert!(spans[5] == Span::new(13, 14, Loc::new(2, 5), Loc::new(2, 6)));
+	assert!(spans[6] == Span::new(15, 16, Loc::new(2, 7), Loc::new(2, 8)));
+	assert!(output[7].token == Token::EndOfSequence);
+	assert!(spans[7] == Span::new(16, 16, Loc::new(2, 8), Loc::new(2, 8)));
+}
+
+#[test]
+fn streaming() {
+	let mut lexer = Lexer::new("x ? y");
+	assert!(lexer.next().unwrap().unwrap().token == Token::Identifier(String::from("x")));
+	match lexer.next() {
+		Some(Err(LexError::UnexpectedChar { found: '?', loc })) => assert!(loc == Loc::new(1, 3)),
+		item => panic!("Unexpected item {:?}", item),
+	}
+	assert!(lexer.next().unwrap().unwrap().token == Token::Identifier(String::from("y")));
+	assert!(lexer.next().unwrap().unwrap().token == Token::EndOfSequence);
+	assert!(lexer.next().is_none());
+}
+
+#[test]
+fn large_input() {
+	let input = "total = total + 1\n".repeat(50_000);
+	let output = lexer(&input).unwrap();
+	// Six tokens (including the new line) for each line plus the end of sequence token.
+	assert!(output.len() == 6 * 50_000 + 1);
+	assert!(output.last().unwrap().span.start == input.len());
+}
+
+#[test]
+fn recovery() {
+	let config = LexerConfig { recover: true, ..LexerConfig::default() };
+	let output = lexer_with_config("x = 0b12 ? \"a\\qb\"\ny = \"oops\nz", config).unwrap();
+	assert!(output.len() == 12);
+	match (&output[2].token, &output[3].token, &output[4].token, &output[8].token) {
+		(
+			Token::Error(LexError::MalformedNumber { .. }),
+			Token::Error(LexError::UnexpectedChar { found: '?', .. }),
+			Token::Error(LexError::UnknownEscape { found: 'q', .. }),
+			Token::Error(LexError::UnterminatedString { .. }),
+		) => {}
+		_ => panic!("Unexpected tokens {:?}", output),
+	}
+	// Lexing carries on normally after each error.
+	assert!(output[5].token == Token::NewLine);
+	assert!(output[6].token == Token::Identifier(String::from("y")));
+	assert!(output[9].token == Token::NewLine);
+	assert!(output[10].token == Token::Identifier(String::from("z")));
+	// Each error token spans the whole of the text it was produced for.
+	assert!((output[2].span.start, output[2].span.end) == (4, 8));
+	assert!((output[3].span.start, output[3].span.end) == (9, 10));
+	assert!((output[4].span.start, output[4].span.end) == (11, 17));
+	assert!((output[8].span.start, output[8].span.end) == (22, 27));
+}
+
+#[test]
+fn lossless() {
+	let source = "x  =  1 // one\nif x == 1 then \\  \n\tprint(\"x\\n\")\nendif  ";
+	let config = LexerConfig { lossless: true, ..LexerConfig::default() };
+	let output = lexer_with_config(source, config).unwrap();
+	assert!(to_source(&output) == source);
+	assert!(output[1].text == "=");
+	assert!(output[1].leading_trivia[0].kind == TriviaKind::Whitespace);
+	assert!(output[1].leading_trivia[0].text == "  ");
+	// The comment is attached to the new line which follows it.
+	assert!(output[3].token == Token::NewLine);
+	let kinds: Vec<TriviaKind> = output[3].leading_trivia.iter().map(|t| t.kind).collect();
+	assert!(kinds == vec![TriviaKind::Whitespace, TriviaKind::Comment]);
+	assert!(output[3].leading_trivia[1].text == "// one");
+	// Trailing whitespace is attached to the end of sequence token.
+	assert!(output.last().unwrap().token == Token::EndOfSequence);
+	assert!(output.last().unwrap().leading_trivia[0].text == "  ");
+}
+
+#[test]
+fn lossless_with_errors() {
+	let source = "x = 0b12 ? \"oops\ny";
+	let config = LexerConfig { lossless: true, recover: true, ..LexerConfig::default() };
+	let output = lexer_with_config(source, config).unwrap();
+	assert!(to_source(&output) == source);
+}
+
+#[test]
+fn indentation() {
+	let output = lexer("if x then\n\ty = 1\n\n\t// note\n\tif y then\n\t\tz = 2\nendif").unwrap();
+	let tokens: Vec<Token> = output.into_iter().map(|token| token.token).collect();
+	let indents: Vec<(usize, &Token)> = tokens
+		.iter()
+		.enumerate()
+		.filter(|(_, token)| **token == Token::Indent || **token == Token::Dedent)
+		.collect();
+	assert!(indents.len() == 4);
+	// The first line of each block is preceded by an indent.
+	assert!(tokens[indents[0].0 + 1] == Token::Identifier(String::from("y")));
+	assert!(tokens[indents[1].0 + 1] == Token::Identifier(String::from("z")));
+	// Both blocks end before the 'endif'.
+	assert!(*indents[2].1 == Token::Dedent && *indents[3].1 == Token::Dedent);
+	assert!(tokens[indents[3].0 + 1] == Token::Keyword(Keyword::EndIf));
+}
+
+#[test]
+fn dedent_at_end_of_input() {
+	// The last line is ended before the block is, whether or not the input ends with a new line.
+	for input in &["while x\n    x = x - 1", "while x\n    x = x - 1\n"] {
+		let output = lexer(input).unwrap();
+		let last: Vec<&Token> = output.iter().rev().take(3).map(|token| &token.token).collect();
+		assert!(last == vec![&Token::EndOfSequence, &Token::Dedent, &Token::NewLine]);
+	}
+}
+
+#[test]
+fn mixed_indentation() {
+	match lexer("if x then\n\t  y = 1\nendif").unwrap_err().as_slice() {
+		[LexError::InconsistentIndentation { loc }] => assert!(loc.line_num == 2),
+		errors => panic!("Unexpected errors {:?}", errors),
+	}
+	match lexer("if x then\n\ty = 1\nendif\nif x then\n    y = 1\nendif").unwrap_err().as_slice() {
+		[LexError::InconsistentIndentation { loc }] => assert!(loc.line_num == 5),
+		errors => panic!("Unexpected errors {:?}", errors),
+	}
+}
+
+#[test]
+fn bad_dedent() {
+	// By default, the line closes the block it is indented less than.
+	let output = lexer("a\n    b\n  c").unwrap();
+	let tokens: Vec<Token> = output.into_iter().map(|token| token.token).collect();
+	let dedent = tokens.iter().position(|token| *token == Token::Dedent).unwrap();
+	assert!(tokens[dedent + 1] == Token::Identifier(String::from("c")));
+	let config = LexerConfig { strict_indentation: true, ..LexerConfig::default() };
+	match lexer_with_config("a\n    b\n  c", config).unwrap_err().as_slice() {
+		[LexError::BadDedent { loc }] => assert!(*loc == Loc::new(3, 3)),
+		errors => panic!("Unexpected errors {:?}", errors),
+	}
+}
+
+#[test]
+fn lossless_indentation() {
+	let source = "if x then\n    y = 1\n  \n    z = 2\nendif\n";
+	let config = LexerConfig { lossless: true, ..LexerConfig::default() };
+	let output = lexer_with_config(source, config).unwrap();
+	assert!(to_source(&output) == source);
+	assert!(output[4].token == Token::Indent);
+	assert!(output[4].text == "    ");
+}
+
+/// Applies `edit` to `source`, and checks that relexing gives the same tokens as lexing the edited
+/// source from scratch, returning the range of tokens which were relexed.
+fn check_relex(source: &str, edit: TextEdit, config: LexerConfig) -> std::ops::Range<usize> {
+	let mut new_source = String::from(source);
+	new_source.replace_range(edit.start..edit.end, &edit.replacement);
+	let old_tokens = lexer_with_config(source, config.clone()).unwrap();
+	let relexed = relex(&old_tokens, &new_source, &edit, config.clone()).unwrap();
+	assert!(relexed.tokens == lexer_with_config(&new_source, config).unwrap());
+	relexed.changed
+}
+
+#[test]
+fn relex_within_a_line() {
+	let source = "x = 1\nif x == 1 then\n\ty = 2\n\tz = 3\nendif\nprint(y)\n";
+	// Replace the "2" with "20 + x".
+	let start = source.find('2').unwrap();
+	let changed =
+		check_relex(source, TextEdit::new(start, start + 1, "20 + x"), LexerConfig::default());
+	// Only the tokens on the edited line (the indent, 'y', '=', '20', '+', 'x' and the new line)
+	// are relexed.
+	assert!(changed.len() == 7);
+}
+
+#[test]
+fn relex_across_lines() {
+	let source = "a = 1\nb = 2\nc = 3\nd = 4\n";
+	let start = source.find("2").unwrap();
+	let end = source.find("3").unwrap();
+	let changed = check_relex(
+		source,
+		TextEdit::new(start, end, "5\nwhile true\n    "),
+		LexerConfig::default(),
+	);
+	assert!(changed.start == 4);
+	// Indenting a line changes the state of the lexer at the end of it, so relexing carries on
+	// until the end of the line after it.
+	let source = "while x\n    y\nb = 2\nc = 3\nd = 4\n";
+	let start = source.find("b").unwrap();
+	let changed = check_relex(source, TextEdit::new(start, start, "    "), LexerConfig::default());
+	assert!(changed == (6..15));
+}
+
+#[test]
+fn relex_indentation_changes() {
+	let config = LexerConfig { recover: true, ..LexerConfig::default() };
+	let source = "if x then\n    a = 1\n    b = 2\nendif\nc = 3\n";
+	let start = source.find("    a").unwrap();
+	check_relex(source, TextEdit::new(start, start + 2, ""), config.clone());
+	let start = source.find("endif").unwrap();
+	check_relex(source, TextEdit::new(start, start, "  "), config.clone());
+	check_relex(source, TextEdit::new(0, source.len(), ""), config.clone());
+	check_relex(source, TextEdit::new(source.len(), source.len(), "d"), config.clone());
+	// Removing the first indented line changes whether later lines are indented with the wrong
+	// character.
+	let source = "a = 1\n\tb = 2\nc = 3\n d = 4\n";
+	let start = source.find('\t').unwrap();
+	check_relex(source, TextEdit::new(start, start + 7, ""), config.clone());
+	// Typing at the end of an indented last line which has no new line after it.
+	let source = "while x\n    y = 1";
+	check_relex(source, TextEdit::new(source.len(), source.len(), " + 2"), config.clone());
+	check_relex(source, TextEdit::new(source.len(), source.len(), "\n"), config.clone());
+	check_relex(source, TextEdit::new(source.len() - 1, source.len(), ""), config);
+}
+
+#[test]
+fn relex_lossless_with_errors() {
+	let config = LexerConfig { lossless: true, recover: true, ..LexerConfig::default() };
+	let source = "x = 1 // one\ny = \"two\"\nz = 3\n";
+	let start = source.find('"').unwrap();
+	let changed = check_relex(source, TextEdit::new(start, start + 1, ""), config.clone());
+	assert!(changed.len() == 5);
+	let start = source.find('z').unwrap();
+	check_relex(source, TextEdit::new(start, start, "? \\\n"), config);
+}
+
+#[test]
+fn relex_large_file() {
+	let source = "total = total + 1\n".repeat(10_000);
+	let start = source.len() / 2;
+	let start = start + source[start..].find('1').unwrap();
+	let changed =
+		check_relex(&source, TextEdit::new(start, start + 1, "2"), LexerConfig::default());
+	assert!(changed.len() <= 6);
+}
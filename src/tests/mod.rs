@@ -1,6 +1,8 @@
 #[cfg(test)]
 mod compile;
 #[cfg(test)]
+mod highlight;
+#[cfg(test)]
 mod lexer;
 #[cfg(test)]
 mod parser;
@@ -1,8 +1,9 @@
 use crate::parser::{lexer::Number, *};
+use indextree::{Arena, NodeId};
 
 #[test]
 fn test_assignment() {
-	let (arena, initial_node) = parser::parser(&mut lexer::lexer(&String::from("x=1")));
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(&String::from("x=1")).unwrap());
 	let x = arena.get(initial_node).unwrap();
 	let assign_operator_node = arena.get(x.first_child().unwrap()).unwrap().get();
 	assert!(assign_operator_node.item == parser::Item::Assign);
@@ -21,6 +22,570 @@ fn test_assignment() {
 				base: String::from("1"),
 				exponent: None,
 				decimal: None,
+				radix: 10,
 			})
 	);
 }
+
+#[test]
+fn comments_are_skipped() {
+	let (arena, initial_node) =
+		parser::parser(&mut lexer::lexer(&String::from("x=1 // a comment")).unwrap());
+	let x = arena.get(initial_node).unwrap();
+	let assign_operator_node = arena.get(x.first_child().unwrap()).unwrap().get();
+	assert!(assign_operator_node.item == parser::Item::Assign);
+}
+
+#[test]
+fn whole_program() {
+	let input = "x=1\n\ny = 2 // a comment\n// another comment\nz = 3\n";
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(input).unwrap());
+	let names = initial_node
+		.children(&arena)
+		.map(|statement| {
+			assert!(arena.get(statement).unwrap().get().item == parser::Item::Assign);
+			let identifier = arena.get(statement).unwrap().first_child().unwrap();
+			arena.get(identifier).unwrap().get().item.clone()
+		})
+		.collect::<Vec<_>>();
+	assert!(
+		names
+			== vec![
+				parser::Item::Identifier(String::from("x")),
+				parser::Item::Identifier(String::from("y")),
+				parser::Item::Identifier(String::from("z")),
+			]
+	);
+}
+
+#[test]
+#[should_panic(
+	expected = "Invalid statement on line 2, column 1. Expected an assignment or a function call."
+)]
+fn bare_identifier_at_end_of_input() {
+	parser::parser(&mut lexer::lexer("x = 1\ny").unwrap());
+}
+
+#[test]
+#[should_panic(
+	expected = "Invalid statement on line 3, column 1. Expected an assignment or a function call."
+)]
+fn misspelled_end_keyword() {
+	parser::parser(&mut lexer::lexer("while x\n\tx = x - 1\nendwhle\n").unwrap());
+}
+
+/// The items of the children of `node`, in order.
+fn child_items(arena: &Arena<parser::Node>, node: NodeId) -> Vec<parser::Item> {
+	node.children(arena).map(|child| arena.get(child).unwrap().get().item.clone()).collect()
+}
+
+#[test]
+fn indented_last_line() {
+	use parser::Item::*;
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer("x = 1\n\ty = 2").unwrap());
+	assert!(child_items(&arena, initial_node) == vec![Assign, Assign]);
+}
+
+#[test]
+fn if_statements() {
+	use parser::Item::*;
+	let input = [
+		"if x == 1 then",
+		"\ty = 1",
+		"elseif x == 2 then",
+		"\ty = 2",
+		"elseif x then",
+		"\ty = 3",
+		"else",
+		"\ty = 4",
+		"\tz = 5",
+		"endif",
+		"w = 6",
+	]
+	.join("\n");
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(&input).unwrap());
+	assert!(child_items(&arena, initial_node) == vec![If, Assign]);
+	let if_node = initial_node.children(&arena).next().unwrap();
+	assert!(child_items(&arena, if_node) == vec![Expression, Block, ElseIf, ElseIf, Block]);
+	let children = if_node.children(&arena).collect::<Vec<_>>();
+	// The condition keeps its operands in order.
+	let condition = arena.get(children[0]).unwrap().first_child().unwrap();
+	assert!(arena.get(condition).unwrap().get().item == Operator(lexer::Operator::DoubleEquals));
+	match &child_items(&arena, condition)[..] {
+		[Identifier(x), Number(_)] => assert!(x == "x"),
+		items => panic!("unexpected condition {:?}", items),
+	}
+	assert!(child_items(&arena, children[1]) == vec![Assign]);
+	assert!(child_items(&arena, children[2]) == vec![Expression, Block]);
+	assert!(child_items(&arena, children[4]) == vec![Assign, Assign]);
+}
+
+#[test]
+fn if_without_else() {
+	use parser::Item::*;
+	let input = "if a then\n\tif b then\n\t\tc = 1\n\tendif\nendif";
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(input).unwrap());
+	let if_node = initial_node.children(&arena).next().unwrap();
+	assert!(child_items(&arena, if_node) == vec![Expression, Block]);
+	let block = if_node.children(&arena).nth(1).unwrap();
+	assert!(child_items(&arena, block) == vec![If]);
+}
+
+#[test]
+#[should_panic(expected = "Missing 'endif' for the 'if' statement on line 2, column 1.")]
+fn if_without_endif() {
+	parser::parser(&mut lexer::lexer("x = 1\nif x then\n\ty = 2\n").unwrap());
+}
+
+#[test]
+#[should_panic(expected = "Missing 'endif' for the 'if' statement on line 1, column 1.")]
+fn if_without_endif_at_end_of_input() {
+	parser::parser(&mut lexer::lexer("if x then\n\ty = 2").unwrap());
+}
+
+#[test]
+#[should_panic(
+	expected = "'else' must be the last branch of the 'if' statement on line 1, but 'else' was \
+	            found on line 5, column 1."
+)]
+fn if_with_two_elses() {
+	let input = "if a then\n\tb = 1\nelse\n\tb = 2\nelse\n\tb = 3\nendif";
+	parser::parser(&mut lexer::lexer(input).unwrap());
+}
+
+#[test]
+#[should_panic(
+	expected = "'else' must be the last branch of the 'if' statement on line 1, but 'elseif' was \
+	            found on line 5, column 1."
+)]
+fn if_with_elseif_after_else() {
+	let input = "if a then\n\tb = 1\nelse\n\tb = 2\nelseif c then\n\tb = 3\nendif";
+	parser::parser(&mut lexer::lexer(input).unwrap());
+}
+
+#[test]
+#[should_panic(expected = "Missing 'endwhile' for the 'while' loop on line 1, column 1.")]
+fn while_without_endwhile_at_end_of_input() {
+	parser::parser(&mut lexer::lexer("while x\n\tprint(x)").unwrap());
+}
+
+#[test]
+fn while_loops() {
+	use parser::Item::*;
+	for input in &[
+		"while x < 10 do\n\tx = x + 1\nendwhile\ny = x",
+		"while x < 10\n\tx = x + 1\nendwhile\ny = x",
+	] {
+		let (arena, initial_node) = parser::parser(&mut lexer::lexer(input).unwrap());
+		assert!(child_items(&arena, initial_node) == vec![While, Assign]);
+		let while_node = initial_node.children(&arena).next().unwrap();
+		assert!(child_items(&arena, while_node) == vec![Expression, Block]);
+		let block = while_node.children(&arena).nth(1).unwrap();
+		assert!(child_items(&arena, block) == vec![Assign]);
+	}
+	// A loop with an empty body.
+	let (arena, initial_node) =
+		parser::parser(&mut lexer::lexer("while running\nendwhile").unwrap());
+	let while_node = initial_node.children(&arena).next().unwrap();
+	let block = while_node.children(&arena).nth(1).unwrap();
+	assert!(child_items(&arena, block) == vec![]);
+}
+
+#[test]
+#[should_panic(expected = "Missing 'endwhile' for the 'while' loop on line 1, column 1.")]
+fn while_without_endwhile() {
+	parser::parser(&mut lexer::lexer("while x do\n\tx = 1\n").unwrap());
+}
+
+#[test]
+fn functions_and_procedures() {
+	use parser::{Item::*, Passing::*};
+	let input = [
+		"function add(a, b)",
+		"\treturn a + b",
+		"endfunction",
+		"procedure greet()",
+		"\tif x then",
+		"\t\treturn",
+		"\tendif",
+		"endprocedure",
+	]
+	.join("\n");
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(&input).unwrap());
+	assert!(child_items(&arena, initial_node) == vec![Function, Procedure]);
+	let function = initial_node.children(&arena).next().unwrap();
+	assert!(
+		child_items(&arena, function)
+			== vec![
+				Identifier("add".to_string()),
+				ParameterList(vec![
+					(Identifier("a".to_string()), ByVal),
+					(Identifier("b".to_string()), ByVal)
+				]),
+				Block,
+			]
+	);
+	let body = function.children(&arena).nth(2).unwrap();
+	assert!(child_items(&arena, body) == vec![Return]);
+	let return_node = body.children(&arena).next().unwrap();
+	assert!(child_items(&arena, return_node) == vec![Expression]);
+
+	let procedure = initial_node.children(&arena).nth(1).unwrap();
+	assert!(child_items(&arena, procedure)[1] == ParameterList(vec![]));
+	let body = procedure.children(&arena).nth(2).unwrap();
+	let if_node = body.children(&arena).next().unwrap();
+	let if_body = if_node.children(&arena).nth(1).unwrap();
+	let return_node = if_body.children(&arena).next().unwrap();
+	assert!(arena.get(return_node).unwrap().get().item == Return);
+	assert!(child_items(&arena, return_node) == vec![]);
+}
+
+#[test]
+#[should_panic(expected = "Missing 'endprocedure' for the procedure on line 1, column 1.")]
+fn procedure_ended_with_endfunction() {
+	parser::parser(&mut lexer::lexer("procedure p()\n\tx = 1\nendfunction").unwrap());
+}
+
+#[test]
+fn parameter_passing() {
+	use parser::{Item::*, Passing::*};
+	let input = "procedure swap(a:byRef, b:byVal, c)\n\tprint(a)\nendprocedure";
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(input).unwrap());
+	let procedure = initial_node.children(&arena).next().unwrap();
+	let parameters = procedure.children(&arena).nth(1).unwrap();
+	assert!(
+		arena[parameters].get().item
+			== ParameterList(vec![
+				(Identifier("a".to_string()), ByRef),
+				(Identifier("b".to_string()), ByVal),
+				(Identifier("c".to_string()), ByVal),
+			])
+	);
+}
+
+#[test]
+#[should_panic(expected = "Expected 'byVal' or 'byRef' after the parameter on line 1, column 15.")]
+fn parameter_with_bad_passing() {
+	parser::parser(&mut lexer::lexer("procedure p(a:b)\n\tprint(a)\nendprocedure").unwrap());
+}
+
+#[test]
+#[should_panic(
+	expected = "Expected a comma or a closing bracket after the parameter on line 1, column 14."
+)]
+fn parameters_without_commas() {
+	parser::parser(&mut lexer::lexer("function f(a b)\n\treturn a\nendfunction").unwrap());
+}
+
+#[test]
+#[should_panic(expected = "Expected a parameter on line 1, column 12.")]
+fn parameter_list_with_only_a_comma() {
+	parser::parser(&mut lexer::lexer("function f(,)\n\treturn 1\nendfunction").unwrap());
+}
+
+#[test]
+fn call_expressions() {
+	use parser::Item::*;
+	let input = "x = max(a + 1, min(b, 2), c) * f()\nprint(\"hi\")\nreset()";
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(input).unwrap());
+	assert!(
+		child_items(&arena, initial_node)
+			== vec![Assign, Call("print".to_string()), Call("reset".to_string())]
+	);
+	let assign = initial_node.children(&arena).next().unwrap();
+	let expression = assign.children(&arena).nth(1).unwrap();
+	let times = expression.children(&arena).next().unwrap();
+	assert!(child_items(&arena, times) == vec![Call("max".to_string()), Call("f".to_string())]);
+	let max = times.children(&arena).next().unwrap();
+	assert!(
+		child_items(&arena, max)
+			== vec![
+				Operator(lexer::Operator::Plus),
+				Call("min".to_string()),
+				Identifier("c".to_string()),
+			]
+	);
+	let min = max.children(&arena).nth(1).unwrap();
+	assert!(child_items(&arena, min)[0] == Identifier("b".to_string()));
+	let f = times.children(&arena).nth(1).unwrap();
+	assert!(child_items(&arena, f) == vec![]);
+	let print = initial_node.children(&arena).nth(1).unwrap();
+	assert!(child_items(&arena, print) == vec![String("hi".to_string())]);
+}
+
+#[test]
+#[should_panic(expected = "Missing a closing bracket on line 1, column 5")]
+fn unclosed_call() {
+	parser::parser(&mut lexer::lexer("x = f(1, 2").unwrap());
+}
+
+#[test]
+fn for_loops() {
+	use parser::Item::*;
+	let input = "for i = 0 to 9\n\tprint(i)\nnext i\nfor j = 10 to 1 step -1\n\tx = j\nendfor";
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(input).unwrap());
+	assert!(child_items(&arena, initial_node) == vec![For, For]);
+	let first = initial_node.children(&arena).next().unwrap();
+	assert!(
+		child_items(&arena, first) == vec![Identifier("i".to_string()), Start, End, Step, Block]
+	);
+	// Without a step, the loop counts up in ones.
+	let step = first.children(&arena).nth(3).unwrap();
+	match &child_items(&arena, step)[..] {
+		[Number(number)] => assert!(number.base == "1"),
+		items => panic!("unexpected step {:?}", items),
+	}
+	let second = initial_node.children(&arena).nth(1).unwrap();
+	let step = second.children(&arena).nth(3).unwrap();
+	assert!(child_items(&arena, step) == vec![Operator(lexer::Operator::Negate)]);
+	let body = second.children(&arena).nth(4).unwrap();
+	assert!(child_items(&arena, body) == vec![Assign]);
+}
+
+#[test]
+fn for_loops_with_do() {
+	use parser::Item::*;
+	let input =
+		"for i = 1 to 10 do\n\tprint(i)\nnext i\nfor j = 10 to 1 step -1 do\n\tx = j\nendfor";
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(input).unwrap());
+	assert!(child_items(&arena, initial_node) == vec![For, For]);
+	for node in initial_node.children(&arena) {
+		let body = node.children(&arena).nth(4).unwrap();
+		assert!(child_items(&arena, body).len() == 1);
+	}
+}
+
+#[test]
+#[should_panic(expected = "Expected a new line following the 'do' keyword on line 1, column 20.")]
+fn for_with_statement_after_do() {
+	parser::parser(&mut lexer::lexer("for i = 1 to 10 do print(i)\nnext i").unwrap());
+}
+
+#[test]
+#[should_panic(
+	expected = "Expected 'next i' (to match the loop on line 1), but found 'next j' on \
+                           line 3, column 6."
+)]
+fn for_with_wrong_next() {
+	parser::parser(&mut lexer::lexer("for i = 1 to 3\n\tx = i\nnext j").unwrap());
+}
+
+#[test]
+#[should_panic(expected = "Missing 'next i' for the 'for' loop on line 1, column 1.")]
+fn for_without_next() {
+	parser::parser(&mut lexer::lexer("for i = 1 to 3\n\tx = i\n").unwrap());
+}
+
+#[test]
+fn boolean_literals() {
+	use parser::Item::*;
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer("x = true or false").unwrap());
+	let assign = initial_node.children(&arena).next().unwrap();
+	let expression = assign.children(&arena).nth(1).unwrap();
+	let or = expression.children(&arena).next().unwrap();
+	assert!(child_items(&arena, or) == vec![Bool(true), Bool(false)]);
+}
+
+#[test]
+fn unary_operators() {
+	use parser::Item::*;
+	// A unary minus binds more tightly than multiplication, but less tightly than exponentiation.
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer("x = 2 * -y ^ 2").unwrap());
+	let assign = initial_node.children(&arena).next().unwrap();
+	let expression = assign.children(&arena).nth(1).unwrap();
+	let times = expression.children(&arena).next().unwrap();
+	assert!(arena[times].get().item == Operator(lexer::Operator::Times));
+	let negate = times.children(&arena).nth(1).unwrap();
+	assert!(child_items(&arena, negate) == vec![Operator(lexer::Operator::Exponent)]);
+}
+
+#[test]
+fn for_in_loops() {
+	use parser::Item::*;
+	for input in &["for name in names\n\tprint(name)\nendfor", "for x in xs do\n\tprint(x)\nnext x"]
+	{
+		let (arena, initial_node) = parser::parser(&mut lexer::lexer(input).unwrap());
+		assert!(child_items(&arena, initial_node) == vec![ForIn]);
+		let for_in = initial_node.children(&arena).next().unwrap();
+		let body = for_in.children(&arena).nth(2).unwrap();
+		assert!(child_items(&arena, body) == vec![Call("print".to_string())]);
+	}
+}
+
+#[test]
+#[should_panic(expected = "Expected a new line following the 'do' keyword on line 1, column 16.")]
+fn for_in_with_statement_after_do() {
+	parser::parser(&mut lexer::lexer("for x in xs do print(x)\nnext x").unwrap());
+}
+
+#[test]
+fn switch_statements() {
+	use parser::Item::*;
+	let input = [
+		"switch day:",
+		"\tcase \"Sat\", \"Sun\":",
+		"\t\tprint(\"weekend\")",
+		"\tcase \"Mon\":",
+		"\tdefault:",
+		"\t\tprint(\"weekday\")",
+		"\t\tx = 1",
+		"endswitch",
+	]
+	.join("\n");
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(&input).unwrap());
+	let switch = initial_node.children(&arena).next().unwrap();
+	assert!(child_items(&arena, switch) == vec![Expression, Case, Case, Default]);
+	let arms = switch.children(&arena).collect::<Vec<_>>();
+	assert!(
+		child_items(&arena, arms[1])
+			== vec![String("Sat".to_string()), String("Sun".to_string()), Block]
+	);
+	let block = arena[arms[2]].last_child().unwrap();
+	assert!(child_items(&arena, block) == vec![]);
+	let block = arena[arms[3]].last_child().unwrap();
+	assert!(child_items(&arena, block) == vec![Call("print".to_string()), Assign]);
+}
+
+#[test]
+#[should_panic(
+	expected = "Duplicate case on line 4, column 10 (the same value is handled on line \
+                           2)."
+)]
+fn switch_with_duplicate_case() {
+	let input = "switch x:\n\tcase 10:\n\t\ty = 1\n\tcase 2, 0xA:\n\t\ty = 2\nendswitch";
+	parser::parser(&mut lexer::lexer(input).unwrap());
+}
+
+#[test]
+#[should_panic(expected = "Missing 'endswitch' for the 'switch' statement on line 1, column 1.")]
+fn switch_without_endswitch() {
+	parser::parser(&mut lexer::lexer("switch x:\n\tcase 1:\n\t\ty = 1\n").unwrap());
+}
+
+#[test]
+#[should_panic(
+	expected = "'default' must be the last arm of the 'switch' statement on line 1, but 'default' \
+	            was found on line 4, column 2."
+)]
+fn switch_with_two_defaults() {
+	let input = "switch x:\n\tdefault:\n\t\ty = 2\n\tdefault:\n\t\ty = 3\nendswitch";
+	parser::parser(&mut lexer::lexer(input).unwrap());
+}
+
+#[test]
+#[should_panic(
+	expected = "'default' must be the last arm of the 'switch' statement on line 1, but 'case' \
+	            was found on line 4, column 2."
+)]
+fn switch_with_case_after_default() {
+	let input = "switch x:\n\tdefault:\n\t\ty = 2\n\tcase 1:\n\t\ty = 3\nendswitch";
+	parser::parser(&mut lexer::lexer(input).unwrap());
+}
+
+#[test]
+fn do_until_loops() {
+	use parser::Item::*;
+	let input = "do\n\tx = input()\nuntil x > 0\nwhile x > 0 do\n\tx = x - 1\nendwhile";
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(input).unwrap());
+	assert!(child_items(&arena, initial_node) == vec![DoUntil, While]);
+	let do_until = initial_node.children(&arena).next().unwrap();
+	assert!(child_items(&arena, do_until) == vec![Block, Expression]);
+	let condition = arena[do_until].last_child().unwrap();
+	assert!(child_items(&arena, condition) == vec![Operator(lexer::Operator::GreaterThan)]);
+}
+
+#[test]
+#[should_panic(expected = "Missing 'until' for the 'do' loop on line 2, column 1.")]
+fn do_without_until() {
+	parser::parser(&mut lexer::lexer("x = 0\ndo\n\tx = x + 1\nendwhile").unwrap());
+}
+
+#[test]
+fn arrays() {
+	use parser::Item::*;
+	let input = [
+		"array names[5]",
+		"array board[3, 3]",
+		"names[2] = \"Bob\"",
+		"board[r, c + 1] = names[0]",
+		"x = [1, [2, 3], []]",
+	]
+	.join("\n");
+	let (arena, initial_node) = parser::parser(&mut lexer::lexer(&input).unwrap());
+	let statements = initial_node.children(&arena).collect::<Vec<_>>();
+	assert!(child_items(&arena, initial_node) == vec![Array, Array, Assign, Assign, Assign]);
+	assert!(child_items(&arena, statements[0])[0] == Identifier("names".to_string()));
+	assert!(child_items(&arena, statements[1]).len() == 3);
+	assert!(child_items(&arena, statements[2]) == vec![Index, Expression]);
+	let target = arena[statements[3]].first_child().unwrap();
+	assert!(
+		child_items(&arena, target)
+			== vec![
+				Identifier("board".to_string()),
+				Identifier("r".to_string()),
+				Operator(lexer::Operator::Plus),
+			]
+	);
+	let value = arena[statements[3]].last_child().unwrap();
+	assert!(child_items(&arena, value) == vec![Index]);
+	let list = arena[arena[statements[4]].last_child().unwrap()].first_child().unwrap();
+	assert!(arena[list].get().item == List);
+	let elements = list.children(&arena).collect::<Vec<_>>();
+	assert!(child_items(&arena, elements[1]).len() == 2);
+	assert!(child_items(&arena, elements[2]) == vec![]);
+}
+
+#[test]
+#[should_panic(expected = "Arrays can have at most two dimensions, but the array on line 1 has 3.")]
+fn array_with_three_dimensions() {
+	parser::parser(&mut lexer::lexer("array cube[2, 2, 2]").unwrap());
+}
+
+#[test]
+#[should_panic(
+	expected = "Arrays can have at most two dimensions, but the index on line 1, column 6 has 3."
+)]
+fn index_with_three_dimensions() {
+	parser::parser(&mut lexer::lexer("x = a[1, 2, 3]").unwrap());
+}
+
+#[test]
+fn indentation_matching_the_blocks() {
+	let input = [
+		"function f(x)",
+		"\tswitch x:",
+		"\t\tcase 1:",
+		"\t\t\tif x > 0 then",
+		"\t\t\t\treturn 1",
+		"\t\t\telse",
+		"\t\t\t\treturn 2",
+		"\t\t\tendif",
+		"\t\tdefault:",
+		"\t\t\treturn 3",
+		"\tendswitch",
+		"endfunction",
+		"y = f(1)",
+	]
+	.join("\n");
+	let (_, _, warnings) = parser::parser_with_warnings(&mut lexer::lexer(&input).unwrap());
+	assert!(warnings.is_empty());
+}
+
+#[test]
+fn indentation_not_matching_the_blocks() {
+	let input = "if x then\ny = 1\n\tendif\nwhile x\n\tx = 0\n\t\tendwhile";
+	let (_, _, warnings) = parser::parser_with_warnings(&mut lexer::lexer(input).unwrap());
+	let messages = warnings.iter().map(|warning| warning.message.as_str()).collect::<Vec<_>>();
+	assert!(
+		messages
+			== vec![
+				"Misleading indentation on line 2, column 1. Expected 1 level of indentation, but \
+				 found 0.",
+				"Misleading indentation on line 3, column 2. Expected 0 levels of indentation, but \
+				 found 1.",
+				"Misleading indentation on line 6, column 3. Expected 0 levels of indentation, but \
+				 found 2.",
+			]
+	);
+	assert!(warnings[0].loc == lexer::Loc::new(2, 1));
+}
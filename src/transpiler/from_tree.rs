@@ -1,20 +1,334 @@
-use crate::parser::{parser, parser::Node};
+use crate::parser::{
+	lexer::{Number, Operator},
+	parser::{Item, Node},
+};
 use indextree::{Arena, NodeId};
 
-fn transpile_block(block_node: &NodeId, arena: &mut Arena<Node>, output: &mut String) {}
+fn child_nodes(node: &NodeId, arena: &Arena<Node>) -> Vec<NodeId> {
+	node.children(arena).collect()
+}
+
+fn transpile_block(block_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	for statement in block_node.children(arena) {
+		transpile_statement(&statement, arena, output);
+	}
+}
+
+fn transpile_statement(statement: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	match &arena[*statement].get().item {
+		Item::Assign => {
+			let children = child_nodes(statement, arena);
+			// Elements of arrays are assigned to, rather than declared.
+			if arena[children[0]].get().item != Item::Index {
+				output.push_str("var ");
+			}
+			transpile_expression(&children[0], arena, output);
+			output.push_str(" = ");
+			transpile_expression(&children[1], arena, output);
+			output.push_str(";\n");
+		}
+		Item::Call(_) => {
+			transpile_expression(statement, arena, output);
+			output.push_str(";\n");
+		}
+		Item::Array => transpile_array(statement, arena, output),
+		Item::If => transpile_if(statement, arena, output),
+		Item::While => transpile_while(statement, arena, output),
+		Item::DoUntil => transpile_do_until(statement, arena, output),
+		Item::For => transpile_for(statement, arena, output),
+		Item::ForIn => transpile_for_in(statement, arena, output),
+		Item::Switch => transpile_switch(statement, arena, output),
+		Item::Function | Item::Procedure => transpile_function(statement, arena, output),
+		Item::Return => {
+			output.push_str("return");
+			if let Some(value) = arena[*statement].first_child() {
+				output.push(' ');
+				transpile_expression(&value, arena, output);
+			}
+			output.push_str(";\n");
+		}
+		item => panic!("Cannot transpile {:?} as a statement.", item),
+	}
+}
+
+fn transpile_array(array_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	let children = child_nodes(array_node, arena);
+	output.push_str("var ");
+	transpile_expression(&children[0], arena, output);
+	output.push_str(" = ");
+	match &children[1..] {
+		[size] => {
+			output.push_str("new Array(");
+			transpile_expression(size, arena, output);
+			output.push(')');
+		}
+		[rows, columns] => {
+			output.push_str("Array.from({ length: ");
+			transpile_expression(rows, arena, output);
+			output.push_str(" }, () => new Array(");
+			transpile_expression(columns, arena, output);
+			output.push_str("))");
+		}
+		_ => panic!("Arrays can only have one or two dimensions."),
+	}
+	output.push_str(";\n");
+}
+
+fn transpile_if(if_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	let children = child_nodes(if_node, arena);
+	output.push_str("if (");
+	transpile_expression(&children[0], arena, output);
+	output.push_str(") {\n");
+	transpile_block(&children[1], arena, output);
+	for branch in &children[2..] {
+		match arena[*branch].get().item {
+			Item::ElseIf => {
+				let branch_children = child_nodes(branch, arena);
+				output.push_str("} else if (");
+				transpile_expression(&branch_children[0], arena, output);
+				output.push_str(") {\n");
+				transpile_block(&branch_children[1], arena, output);
+			}
+			_ => {
+				output.push_str("} else {\n");
+				transpile_block(branch, arena, output);
+			}
+		}
+	}
+	output.push_str("}\n");
+}
+
+fn transpile_number(number: &Number, output: &mut String) {
+	match number.radix {
+		16 => output.push_str("0x"),
+		8 => output.push_str("0o"),
+		2 => output.push_str("0b"),
+		_ => {}
+	}
+	if number.radix == 10 {
+		// Javascript reads decimal literals with a leading zero as octal, or rejects them in strict
+		// mode.
+		let base = number.base.trim_start_matches('0');
+		output.push_str(if base.is_empty() { "0" } else { base });
+	} else {
+		output.push_str(&number.base);
+	}
+	if let Some(decimal) = &number.decimal {
+		output.push('.');
+		output.push_str(decimal);
+	}
+	if let Some(exponent) = &number.exponent {
+		output.push('e');
+		output.push_str(exponent);
+	}
+}
 
-fn transpile_if(if_node: &NodeId, arena: &mut Arena<Node>, output: &mut String) {}
+fn transpile_operator(operator: &Operator) -> &'static str {
+	match operator {
+		Operator::Plus => "+",
+		Operator::Minus | Operator::Negate => "-",
+		Operator::Times => "*",
+		Operator::Divide => "/",
+		Operator::Modulo => "%",
+		Operator::Exponent => "**",
+		Operator::And => "&&",
+		Operator::Or => "||",
+		Operator::Not => "!",
+		Operator::Equals | Operator::DoubleEquals => "===",
+		Operator::NotEquals => "!==",
+		Operator::GreaterThan => ">",
+		Operator::LessThan => "<",
+		Operator::GreaterThanOrEqualTo => ">=",
+		Operator::LessThanOrEqualTo => "<=",
+		Operator::IntegerDivide | Operator::In | Operator::Empty => {
+			panic!("{:?} has no equivalent Javascript operator.", operator)
+		}
+	}
+}
+
+fn transpile_expression(expression_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	let children = child_nodes(expression_node, arena);
+	match &arena[*expression_node].get().item {
+		Item::Expression | Item::Start | Item::End | Item::Step => {
+			transpile_expression(&children[0], arena, output)
+		}
+		Item::Identifier(name) => output.push_str(name),
+		Item::Number(number) => transpile_number(number, output),
+		// Rust's escapes for strings are all understood by Javascript.
+		Item::String(string) => output.push_str(&format!("{:?}", string)),
+		Item::Bool(value) => output.push_str(if *value { "true" } else { "false" }),
+		Item::Call(name) => {
+			output.push_str(match name.as_str() {
+				"print" => "console.log",
+				"input" => "prompt",
+				_ => name,
+			});
+			output.push('(');
+			for (index, argument) in children.iter().enumerate() {
+				if index > 0 {
+					output.push_str(", ");
+				}
+				transpile_expression(argument, arena, output);
+			}
+			output.push(')');
+		}
+		Item::Index => {
+			transpile_expression(&children[0], arena, output);
+			// Elements of two dimensional arrays are stored as arrays of arrays.
+			for index in &children[1..] {
+				output.push('[');
+				transpile_expression(index, arena, output);
+				output.push(']');
+			}
+		}
+		Item::List => {
+			output.push('[');
+			for (index, element) in children.iter().enumerate() {
+				if index > 0 {
+					output.push_str(", ");
+				}
+				transpile_expression(element, arena, output);
+			}
+			output.push(']');
+		}
+		// Brackets are always added, so that the precedence of the pseudocode operators is kept.
+		Item::Operator(Operator::IntegerDivide) => {
+			output.push_str("Math.trunc(");
+			transpile_expression(&children[0], arena, output);
+			output.push_str(" / ");
+			transpile_expression(&children[1], arena, output);
+			output.push(')');
+		}
+		// Arrays and strings both have an `includes` method.
+		Item::Operator(Operator::In) => {
+			output.push('(');
+			transpile_expression(&children[1], arena, output);
+			output.push_str(".includes(");
+			transpile_expression(&children[0], arena, output);
+			output.push_str("))");
+		}
+		Item::Operator(operator) if children.len() == 1 => {
+			output.push('(');
+			output.push_str(transpile_operator(operator));
+			transpile_expression(&children[0], arena, output);
+			output.push(')');
+		}
+		Item::Operator(operator) => {
+			output.push('(');
+			transpile_expression(&children[0], arena, output);
+			output.push(' ');
+			output.push_str(transpile_operator(operator));
+			output.push(' ');
+			transpile_expression(&children[1], arena, output);
+			output.push(')');
+		}
+		item => panic!("Cannot transpile {:?} as an expression.", item),
+	}
+}
 
-fn transpile_expression(expression_node: &NodeId, arena: &mut Arena<Node>, output: &mut String) {}
+fn transpile_function(function_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	let children = child_nodes(function_node, arena);
+	output.push_str("function ");
+	transpile_expression(&children[0], arena, output);
+	output.push('(');
+	if let Item::ParameterList(parameters) = &arena[children[1]].get().item {
+		// Javascript passes arrays by reference and everything else by value, which covers how
+		// parameters passed by reference are used in practice (to change the array passed in).
+		for (index, (parameter, _)) in parameters.iter().enumerate() {
+			if index > 0 {
+				output.push_str(", ");
+			}
+			if let Item::Identifier(name) = parameter {
+				output.push_str(name);
+			}
+		}
+	}
+	output.push_str(") {\n");
+	transpile_block(&children[2], arena, output);
+	output.push_str("}\n");
+}
 
-fn transpile_function(function_node: &NodeId, arena: &mut Arena<Node>, output: &mut String) {}
+fn transpile_while(while_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	let children = child_nodes(while_node, arena);
+	output.push_str("while (");
+	transpile_expression(&children[0], arena, output);
+	output.push_str(") {\n");
+	transpile_block(&children[1], arena, output);
+	output.push_str("}\n");
+}
 
-fn transpile_while(while_node: &NodeId, arena: &mut Arena<Node>, output: &mut String) {}
+fn transpile_do_until(do_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	let children = child_nodes(do_node, arena);
+	output.push_str("do {\n");
+	transpile_block(&children[0], arena, output);
+	output.push_str("} while (!");
+	transpile_expression(&children[1], arena, output);
+	output.push_str(");\n");
+}
 
-fn transpile_for(for_node: &NodeId, arena: &mut Arena<Node>, output: &mut String) {}
+fn transpile_for(for_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	let children = child_nodes(for_node, arena);
+	let mut variable = String::new();
+	transpile_expression(&children[0], arena, &mut variable);
+	// The end and the step are only worked out once, before the loop starts. The loop counts down
+	// if the step is negative.
+	let (end, step) = (format!("$end_{}", variable), format!("$step_{}", variable));
+	output.push_str(&format!("for (var {} = ", variable));
+	transpile_expression(&children[1], arena, output);
+	output.push_str(&format!(", {} = ", end));
+	transpile_expression(&children[2], arena, output);
+	output.push_str(&format!(", {} = ", step));
+	transpile_expression(&children[3], arena, output);
+	output.push_str(&format!(
+		"; {step} >= 0 ? {var} <= {end} : {var} >= {end}; {var} += {step}) {{\n",
+		step = step,
+		var = variable,
+		end = end
+	));
+	transpile_block(&children[4], arena, output);
+	output.push_str("}\n");
+}
+
+fn transpile_for_in(for_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	let children = child_nodes(for_node, arena);
+	// `for ... of` goes through arrays element by element, and strings character by character.
+	output.push_str("for (var ");
+	transpile_expression(&children[0], arena, output);
+	output.push_str(" of ");
+	transpile_expression(&children[1], arena, output);
+	output.push_str(") {\n");
+	transpile_block(&children[2], arena, output);
+	output.push_str("}\n");
+}
+
+fn transpile_switch(switch_node: &NodeId, arena: &Arena<Node>, output: &mut String) {
+	let children = child_nodes(switch_node, arena);
+	output.push_str("switch (");
+	transpile_expression(&children[0], arena, output);
+	output.push_str(") {\n");
+	for arm in &children[1..] {
+		let arm_children = child_nodes(arm, arena);
+		let (block, values) = arm_children.split_last().unwrap();
+		match arena[*arm].get().item {
+			Item::Default => output.push_str("default:\n"),
+			_ => {
+				// Javascript compares cases using `===`, which matches `==` in pseudocode.
+				for value in values {
+					output.push_str("case ");
+					transpile_expression(value, arena, output);
+					output.push_str(":\n");
+				}
+			}
+		}
+		transpile_block(block, arena, output);
+		// Pseudocode cases never fall through to the next one.
+		output.push_str("break;\n");
+	}
+	output.push_str("}\n");
+}
 
 pub fn from_tree(tree: Arena<Node>, start_node: NodeId) -> String {
 	let mut output = String::new();
-	for node in start_node.ancestors(&tree) {}
+	transpile_block(&start_node, &tree, &mut output);
 	output
 }